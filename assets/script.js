@@ -64,3 +64,17 @@ document.getElementById('hand-form')?.addEventListener('submit', (event) => {
     event.preventDefault();
   }
 });
+
+// The name people save forms under is remembered on their device.
+document.querySelectorAll('input[name="author"]').forEach((input) => {
+  input.value ||= localStorage.getItem('author') || '';
+  input.addEventListener('change', () => localStorage.setItem('author', input.value));
+});
+
+// Puts the rows of the hands table next to `select` in the order it names, by their data attributes.
+function sortHandsTable(select) {
+  const tbody = select.closest('section').querySelector('table.hands tbody');
+  const rows = Array.from(tbody.querySelectorAll('tr'));
+  rows.sort((a, b) => a.dataset[select.value] - b.dataset[select.value]);
+  rows.forEach((row) => tbody.appendChild(row));
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use maud::{html, Markup};
+
+use crate::game::CompletedHand;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 300.0;
+const MARGIN_LEFT: f64 = 50.0;
+const MARGIN_RIGHT: f64 = 10.0;
+const MARGIN_Y: f64 = 10.0;
+
+const COLOURS: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+    "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+
+fn player_colour(index: usize) -> &'static str {
+    COLOURS[index % COLOURS.len()]
+}
+
+/// An inline SVG line chart of each player's cumulative score, hand by hand.
+///
+/// `running_totals` must line up with `hands_with_scores` (see `scoring::running_totals`). Each
+/// player's line starts at 0 just before the first hand they played.
+pub fn score_chart(
+    players: &[String],
+    hands_with_scores: &[(CompletedHand, HashMap<String, i32>)],
+    running_totals: &[HashMap<String, i32>],
+) -> Markup {
+    let hand_count = running_totals.len().max(1) as f64;
+    let all_totals = running_totals.iter().flat_map(|totals| totals.values());
+    let max = all_totals.clone().copied().max().unwrap_or(0).max(0) as f64;
+    let min = all_totals.copied().min().unwrap_or(0).min(0) as f64;
+    let range = if max > min { max - min } else { 1.0 };
+
+    let x = |i: usize| MARGIN_LEFT + (WIDTH - MARGIN_LEFT - MARGIN_RIGHT) * i as f64 / hand_count;
+    let y = |total: i32| MARGIN_Y + (HEIGHT - 2.0 * MARGIN_Y) * (max - total as f64) / range;
+
+    html! {
+        svg .score-chart xmlns="http://www.w3.org/2000/svg" viewBox=(format!("0 0 {} {}", WIDTH, HEIGHT)) role="img" {
+            title { "Évolution des scores" }
+            line .axis x1=(MARGIN_LEFT) x2=((WIDTH - MARGIN_RIGHT)) y1=(y(0)) y2=(y(0)) {}
+            line .axis x1=(MARGIN_LEFT) x2=(MARGIN_LEFT) y1=(MARGIN_Y) y2=((HEIGHT - MARGIN_Y)) {}
+            @for value in [max as i32, 0, min as i32] {
+                text .axis-label x=((MARGIN_LEFT - 5.0)) y=(y(value)) text-anchor="end" dominant-baseline="middle" { (value) }
+            }
+
+            @for (player_index, player) in players.iter().enumerate() {
+                @let colour = player_colour(player_index);
+                @let points = player_points(player, running_totals);
+                @if let Some((first_index, _)) = points.first() {
+                    @let polyline = std::iter::once(format!("{:.1},{:.1}", x(*first_index), y(0)))
+                        .chain(points.iter().map(|(i, total)| format!("{:.1},{:.1}", x(i + 1), y(*total))))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    polyline points=(polyline) fill="none" stroke=(colour) stroke-width="2" {}
+                    @for (i, total) in &points {
+                        @if let Some(score) = hands_with_scores[*i].1.get(player) {
+                            @let hand = &hands_with_scores[*i].0;
+                            circle cx=(format!("{:.1}", x(i + 1))) cy=(format!("{:.1}", y(*total))) r="4" fill=(colour) {
+                                title { (player) " — table " (hand.table) ", partie #" (hand.hand_number) " : " (format!("{:+}", score)) ", total " (total) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        ul .chart-legend {
+            @for (player_index, player) in players.iter().enumerate() {
+                li {
+                    span .swatch style=(format!("background-color: {}", player_colour(player_index))) {}
+                    (player)
+                }
+            }
+        }
+    }
+}
+
+/// The (hand index, running total) pairs from the first hand `player` played onwards.
+fn player_points(player: &str, running_totals: &[HashMap<String, i32>]) -> Vec<(usize, i32)> {
+    running_totals
+        .iter()
+        .enumerate()
+        .filter_map(|(i, totals)| totals.get(player).map(|total| (i, *total)))
+        .collect()
+}
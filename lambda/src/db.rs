@@ -1,23 +1,45 @@
+// The SDK's error types are large, and boxing them would lose the `?` conversions below.
+#![allow(clippy::result_large_err)]
+
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 use lazy_static::lazy_static;
 
 use aws_sdk_dynamodb::error::SdkError;
 use aws_sdk_dynamodb::operation::{
-    get_item::GetItemError, put_item::PutItemError, delete_item::DeleteItemError, query::QueryError,
+    batch_get_item::BatchGetItemError, batch_write_item::BatchWriteItemError, get_item::GetItemError, put_item::PutItemError, delete_item::DeleteItemError,
+    query::QueryError, scan::ScanError, transact_write_items::TransactWriteItemsError, update_item::UpdateItemError,
 };
-use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::types::{AttributeValue, Delete, DeleteRequest, KeysAndAttributes, Put, TransactWriteItem, Update, WriteRequest};
+use aws_sdk_dynamodb::primitives::{DateTime, DateTimeFormat as Format};
 use aws_sdk_dynamodb::Client;
 use thiserror::Error;
+use uuid::Uuid;
 
-use crate::game::{Bid, Chelem, CompletedHand, Game, Poignée};
+use crate::game::{Bid, Chelem, CompletedHand, Confirmation, Game, GameSearch, GameStatus, Player, Poignée, RankingMethod};
+use crate::history::{Change, HistoryEntry};
+use crate::names::name_key;
+use crate::scoring::{score, GameListing, Tallies};
 
 lazy_static! {
     static ref APP_NAME: String = std::env::var("APP_NAME").unwrap_or("tarot".to_string());
     static ref TABLE_GAMES: String = format!("{}-games", *APP_NAME);
     static ref TABLE_HANDS: String = format!("{}-hands", *APP_NAME);
+    static ref TABLE_PLAYERS: String = format!("{}-players", *APP_NAME);
+    static ref TABLE_SUBMISSIONS: String = format!("{}-submissions", *APP_NAME);
+    static ref TABLE_HISTORY: String = format!("{}-history", *APP_NAME);
+    /// The registry as last read by this Lambda instance, and when.
+    static ref REGISTRY: Mutex<Option<(Instant, Vec<Player>)>> = Mutex::new(None);
 }
 
+pub mod migrations;
+
+/// Index of `TABLE_GAMES` on (`listing`, `date`), in which every listed game has the same `listing`.
+const INDEX_GAMES_BY_DATE: &str = "listing-date-index";
+const LISTING: &str = "games";
+
 #[derive(Error, Debug)]
 pub enum DbError {
     #[error("Validation error: {0}")]
@@ -35,6 +57,33 @@ pub enum DbError {
     #[error("DeleteItem error: {0}")]
     DeleteItem(#[from] SdkError<DeleteItemError>),
 
+    #[error("UpdateItem error: {0}")]
+    UpdateItem(#[from] SdkError<UpdateItemError>),
+
+    #[error("Scan error: {0}")]
+    Scan(#[from] SdkError<ScanError>),
+
+    #[error("BatchGetItem error: {0}")]
+    BatchGetItem(#[from] SdkError<BatchGetItemError>),
+
+    #[error("BatchWriteItem error: {0}")]
+    BatchWriteItem(#[from] SdkError<BatchWriteItemError>),
+
+    #[error("TransactWriteItems error: {0}")]
+    TransactWriteItems(#[from] SdkError<TransactWriteItemsError>),
+
+    /// Writes that must all be made together, but are more than one transaction takes.
+    #[error("Too many writes for one transaction: {0}")]
+    TooManyWrites(usize),
+
+    /// A conditional write found that someone else had saved the item first.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// A write to a game's hands found that the game had been finished (or deleted) first.
+    #[error("Game {0:?} is locked")]
+    Locked(String),
+
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 }
@@ -48,33 +97,783 @@ pub async fn get_game(client: &Client, game_id: &str) -> Result<Option<Game>, Db
         .await?;
 
     if let Some(item) = result.item {
-        let game = Game {
-            game_id: get_s(&item, "gameId")?,
-            date: get_s(&item, "date")?,
-            host: get_s(&item, "host")?,
-            players: get_l_of_s(&item, "players")?,
-            tables: get_l_of_s(&item, "tables")?
-        };
-        Ok(Some(game))
+        let registry = get_players_by_id(client, &item_player_ids(&item)?).await?;
+        Ok(Some(item_to_game(item, &registry)?))
     } else {
         Ok(None)
     }
 }
 
-pub async fn put_game(client: &Client, game: &Game) -> Result<(), DbError> {
+/// A page of the games that aren't archived and match `search`, most recent first, and the cursor
+/// for the next page (`{date}/{gameId}`) if there is one.  Players are searched for as `players`,
+/// the registry players that `search.player` matches.
+///
+/// Filtered pages may come back with fewer games than `page_size` (or more), since DynamoDB
+/// applies the filter after reading each page.
+pub async fn list_games(
+    client: &Client,
+    search: &GameSearch,
+    players: &[Player],
+    cursor: Option<&(String, String)>,
+    page_size: i32,
+) -> Result<(Vec<GameListing>, Option<String>), DbError> {
+    let mut values = HashMap::from([(":listing".to_string(), to_s(LISTING))]);
+    let mut key_condition = "listing = :listing".to_string();
+    match (search.from.is_empty(), search.to.is_empty()) {
+        (false, false) => key_condition.push_str(" AND #date BETWEEN :from AND :to"),
+        (false, true) => key_condition.push_str(" AND #date >= :from"),
+        (true, false) => key_condition.push_str(" AND #date <= :to"),
+        (true, true) => {},
+    }
+    if !search.from.is_empty() {
+        values.insert(":from".to_string(), to_s(&search.from));
+    }
+    if !search.to.is_empty() {
+        values.insert(":to".to_string(), to_s(&search.to));
+    }
+
+    let mut filters = vec![];
+    if !search.host.is_empty() {
+        filters.push("contains(hostKey, :host)".to_string());
+        values.insert(":host".to_string(), to_s(&name_key(&search.host)));
+    }
+    if !search.player.is_empty() {
+        let mut conditions = vec![];
+        for (i, player) in players.iter().enumerate() {
+            values.insert(format!(":player{}", i), to_s(&player.player_id));
+            conditions.push(format!("contains(playerIds, :player{})", i));
+            // Games saved before the registry list their players by name.
+            for (j, name) in std::iter::once(&player.display_name).chain(player.aliases.iter()).enumerate() {
+                values.insert(format!(":player{}name{}", i, j), to_s(name));
+                conditions.push(format!("contains(players, :player{}name{})", i, j));
+            }
+        }
+        if conditions.is_empty() {
+            return Ok((vec![], None));
+        }
+        filters.push(format!("({})", conditions.join(" OR ")));
+    }
+    let filter_expression = if filters.is_empty() { None } else { Some(filters.join(" AND ")) };
+    let names = if key_condition.contains("#date") { Some(HashMap::from([("#date".to_string(), "date".to_string())])) } else { None };
+
+    let mut items = vec![];
+    let mut exclusive_start_key = cursor.map(|(date, game_id)| HashMap::from([
+        ("listing".to_string(), to_s(LISTING)),
+        ("date".to_string(), to_s(date)),
+        ("gameId".to_string(), to_s(game_id)),
+    ]));
+    loop {
+        let result = client
+            .query()
+            .table_name((*TABLE_GAMES).clone())
+            .index_name(INDEX_GAMES_BY_DATE)
+            .key_condition_expression(&key_condition)
+            .set_filter_expression(filter_expression.clone())
+            .set_expression_attribute_names(names.clone())
+            .set_expression_attribute_values(Some(values.clone()))
+            .scan_index_forward(false)
+            .limit(page_size)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await?;
+        items.extend(result.items.unwrap_or_default());
+        exclusive_start_key = result.last_evaluated_key;
+        if exclusive_start_key.is_none() || items.len() >= page_size as usize {
+            break;
+        }
+    }
+
+    let mut player_ids = vec![];
+    for item in &items {
+        player_ids.extend(item_player_ids(item)?);
+    }
+    player_ids.sort();
+    player_ids.dedup();
+    let registry = get_players_by_id(client, &player_ids).await?;
+    let games = items
+        .into_iter()
+        .map(|item| {
+            let HandTallies { hand_count, tallies } = item_tallies(&item)?;
+            let tallies = tallies.map_players(|key| registry.get(&key).map(|p| p.display_name.clone()).unwrap_or(key));
+            Ok(GameListing::new(item_to_game(item, &registry)?, hand_count, &tallies))
+        })
+        .collect::<Result<Vec<GameListing>, DbError>>()?;
+
+    let next_cursor = match exclusive_start_key {
+        Some(key) => Some(format!("{}/{}", get_s(&key, "date")?, get_s(&key, "gameId")?)),
+        None => None,
+    };
+    Ok((games, next_cursor))
+}
+
+/// Games saved before the player registry existed store names rather than player IDs.
+fn item_player_ids(item: &HashMap<String, AttributeValue>) -> Result<Vec<String>, DbError> {
+    if item.contains_key("playerIds") {
+        get_l_of_s(item, "playerIds")
+    } else {
+        Ok(vec![])
+    }
+}
+
+fn item_to_game(item: HashMap<String, AttributeValue>, registry: &HashMap<String, Player>) -> Result<Game, DbError> {
+    let game_id = get_s(&item, "gameId")?;
+    let (players, player_ids) = if item.contains_key("playerIds") {
+        let player_ids = get_l_of_s(&item, "playerIds")?;
+        let players = player_ids
+            .iter()
+            .map(|id| match registry.get(id) {
+                Some(player) => Ok(player.display_name.clone()),
+                None => Err(DbError::Validation(format!("Unknown player {:?} in game {:?}", id, game_id))),
+            })
+            .collect::<Result<Vec<String>, DbError>>()?;
+        (players, player_ids)
+    } else {
+        (get_l_of_s(&item, "players")?, vec![])
+    };
+
+    Ok(Game {
+        game_id,
+        date: get_s(&item, "date")?,
+        host: get_s(&item, "host")?,
+        players,
+        player_ids,
+        tables: get_l_of_s(&item, "tables")?,
+        // Games created before ranking methods existed are ranked by total.
+        ranking_method: match get_option_s(&item, "rankingMethod")? {
+            Some(s) => RankingMethod::from_str(&s)
+                .map_err(|e| DbError::Validation(format!("Invalid rankingMethod {:?}", e.to_string())))?,
+            None => RankingMethod::Total,
+        },
+        // Games created before confirmation existed don't need it.
+        confirmation: match get_option_s(&item, "confirmation")? {
+            Some(s) => Confirmation::from_str(&s)
+                .map_err(|e| DbError::Validation(format!("Invalid confirmation {:?}", e.to_string())))?,
+            None => Confirmation::Off,
+        },
+        // Games created before the lifecycle existed are left open.
+        status: match get_option_s(&item, "status")? {
+            Some(s) => GameStatus::from_str(&s)
+                .map_err(|e| DbError::Validation(format!("Invalid status {:?}", e.to_string())))?,
+            None => GameStatus::InProgress,
+        },
+        final_standings: item_final_standings(&item)?,
+        archived: item.contains_key("archived") && get_bool(&item, "archived")?,
+        version: get_version(&item)?,
+    })
+}
+
+/// A finished game's standings, which refer to players by name as they were when it finished.
+fn item_final_standings(item: &HashMap<String, AttributeValue>) -> Result<Vec<(String, f64)>, DbError> {
+    match item.get("finalStandings") {
+        None => Ok(vec![]),
+        Some(AttributeValue::L(l)) => l
+            .iter()
+            .map(|v| match v {
+                AttributeValue::M(m) => {
+                    let score = match m.get("score") {
+                        Some(AttributeValue::N(n)) => n.parse::<f64>()
+                            .map_err(|e| DbError::Validation(format!("Can't parse final score {:?}: {:?}", n, e)))?,
+                        v => return Err(DbError::Validation(format!("Final score is not a number: {:?}", v))),
+                    };
+                    Ok((get_s(m, "player")?, score))
+                },
+                v => Err(DbError::Validation(format!("Item in attribute \"finalStandings\" is not a map: {:?}", v))),
+            })
+            .collect(),
+        Some(v) => Err(DbError::Validation(format!("Attribute \"finalStandings\" is not a list: {:?}", v))),
+    }
+}
+
+/// The condition for saving over an item loaded at `version`.  Items saved before versions
+/// existed are read as version 0, like items that don't exist yet.
+fn version_condition(version: i32) -> (String, Option<HashMap<String, AttributeValue>>) {
+    if version == 0 {
+        ("attribute_not_exists(version)".to_string(), None)
+    } else {
+        ("version = :version".to_string(), Some(HashMap::from([(":version".to_string(), to_n(version))])))
+    }
+}
+
+/// The condition for writing over or deleting a hand loaded at `version`, which must still exist
+/// outside the trash: on its own, `attribute_not_exists(version)` would also hold for a hand
+/// that's gone.
+fn existing_hand_condition(version: i32) -> (String, Option<HashMap<String, AttributeValue>>) {
+    let (condition, values) = version_condition(version);
+    (format!("attribute_exists(handId) AND attribute_not_exists(deletedAt) AND {}", condition), values)
+}
+
+fn service_error<E, R>(e: &SdkError<E, R>) -> Option<&E> {
+    match e {
+        SdkError::ServiceError(err) => Some(err.err()),
+        _ => None,
+    }
+}
+
+fn game_item(game: &Game) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::from([
+        ("gameId".to_string(), to_s(&game.game_id)),
+        ("date".to_string(), to_s(&game.date)),
+        ("host".to_string(), to_s(&game.host)),
+        ("hostKey".to_string(), to_s(&name_key(&game.host))),
+        ("tables".to_string(), to_l_of_s(&game.tables)),
+        ("rankingMethod".to_string(), AttributeValue::S(game.ranking_method.to_string())),
+        ("confirmation".to_string(), AttributeValue::S(game.confirmation.to_string())),
+        ("status".to_string(), AttributeValue::S(game.status.to_string())),
+        ("archived".to_string(), AttributeValue::Bool(game.archived)),
+        ("version".to_string(), to_n(game.version + 1)),
+    ]);
+    // The listing index is sparse: archived games are left out of it.
+    if !game.archived {
+        item.insert("listing".to_string(), to_s(LISTING));
+    }
+    if !game.final_standings.is_empty() {
+        let standings = game.final_standings
+            .iter()
+            .map(|(player, score)| AttributeValue::M(HashMap::from([
+                ("player".to_string(), to_s(player)),
+                ("score".to_string(), AttributeValue::N(score.to_string())),
+            ])))
+            .collect();
+        item.insert("finalStandings".to_string(), AttributeValue::L(standings));
+    }
+    if game.player_ids.is_empty() {
+        item.insert("players".to_string(), to_l_of_s(&game.players));
+    } else {
+        item.insert("playerIds".to_string(), to_l_of_s(&game.player_ids));
+    }
+    item
+}
+
+/// The most writes DynamoDB accepts in one transaction.
+const MAX_TRANSACTION_WRITES: usize = 100;
+
+/// How many hands a game has, and the tallies of those that count towards its ranking, by
+/// player ID (or by name, for games saved before the registry).  Kept on the game's item so that the list of games doesn't
+/// have to read and score every game's hands.
+#[derive(Debug, Clone, Default)]
+struct HandTallies {
+    hand_count: i32,
+    tallies: Tallies,
+}
+
+impl HandTallies {
+    fn of(game: &Game, hands: &[CompletedHand]) -> Result<HandTallies, DbError> {
+        let mut tallies = HandTallies::default();
+        for hand in hands {
+            tallies.add(game, hand, 1)?;
+        }
+        Ok(tallies)
+    }
+
+    /// Counts `hand` of `game` once more (`sign` 1) or once less (`sign` -1).
+    fn add(&mut self, game: &Game, hand: &CompletedHand, sign: i32) -> Result<(), DbError> {
+        self.hand_count += sign;
+        if game.counts(hand) {
+            let scores = score(hand).map_err(|e| DbError::Validation(format!("Can't score hand {}: {}", hand.hand_id(), e)))?;
+            let scores = scores.into_iter()
+                .map(|(name, score)| (game.player_id(&name).cloned().unwrap_or(name), score))
+                .collect();
+            self.tallies.add(hand, &scores, sign);
+        }
+        Ok(())
+    }
+
+    /// Adds `change` to these tallies, leaving out players who no longer have a hand in the game.
+    fn merge(&mut self, change: &HandTallies) {
+        self.hand_count += change.hand_count;
+        for (key, total) in &change.tallies.totals {
+            *self.tallies.totals.entry(key.clone()).or_default() += total;
+        }
+        for (key, hand_count) in &change.tallies.hand_counts {
+            *self.tallies.hand_counts.entry(key.clone()).or_default() += hand_count;
+        }
+        for (key, weighted_total) in &change.tallies.weighted_totals {
+            *self.tallies.weighted_totals.entry(key.clone()).or_default() += weighted_total;
+        }
+        let played: Vec<String> = self.tallies.hand_counts.iter().filter(|(_, n)| **n > 0).map(|(key, _)| key.clone()).collect();
+        self.tallies.totals.retain(|key, _| played.contains(key));
+        self.tallies.hand_counts.retain(|key, _| played.contains(key));
+        self.tallies.weighted_totals.retain(|key, _| played.contains(key));
+    }
+
+    /// The game item's attributes for the tallies, which are top-level so that `ADD` can update
+    /// them whether or not a game has them yet.
+    fn attributes(&self) -> HashMap<String, AttributeValue> {
+        let mut attributes = HashMap::from([("handCount".to_string(), to_n(self.hand_count))]);
+        for (key, total) in &self.tallies.totals {
+            attributes.insert(format!("total#{}", key), to_n(*total));
+        }
+        for (key, hand_count) in &self.tallies.hand_counts {
+            attributes.insert(format!("hands#{}", key), to_n(*hand_count));
+        }
+        for (key, weighted_total) in &self.tallies.weighted_totals {
+            attributes.insert(format!("weighted#{}", key), AttributeValue::N(weighted_total.to_string()));
+        }
+        attributes
+    }
+}
+
+/// The tallies stored on a game item, as `HandTallies::attributes` writes them.
+fn item_tallies(item: &HashMap<String, AttributeValue>) -> Result<HandTallies, DbError> {
+    let mut tallies = HandTallies::default();
+    if item.contains_key("handCount") {
+        tallies.hand_count = get_n(item, "handCount")?;
+    }
+    for (attribute, value) in item {
+        let Some((kind, key)) = attribute.split_once('#') else { continue };
+        let n = match value {
+            AttributeValue::N(n) => n,
+            v => return Err(DbError::Validation(format!("Attribute {:?} is not a number: {:?}", attribute, v))),
+        };
+        let invalid = || DbError::Validation(format!("Can't parse attribute {:?}: {:?}", attribute, n));
+        match kind {
+            "total" => { tallies.tallies.totals.insert(key.to_string(), n.parse().map_err(|_| invalid())?); },
+            "hands" => { tallies.tallies.hand_counts.insert(key.to_string(), n.parse().map_err(|_| invalid())?); },
+            "weighted" => { tallies.tallies.weighted_totals.insert(key.to_string(), n.parse().map_err(|_| invalid())?); },
+            _ => {},
+        }
+    }
+    Ok(tallies)
+}
+
+/// A game's item, with its tallies.
+fn tallied_game_item(game: &Game, tallies: &HandTallies) -> HashMap<String, AttributeValue> {
+    let mut item = game_item(game);
+    item.extend(tallies.attributes());
+    item.insert("tallied".to_string(), AttributeValue::Bool(true));
+    item
+}
+
+/// A hand being saved under a new key, in another game or with a new hand ID (e.g. because its
+/// table was renamed).
+#[derive(Debug, Clone)]
+pub struct HandMove {
+    pub from_game_id: String,
+    pub from_hand_id: String,
+    pub to_game_id: String,
+    pub hand: CompletedHand,
+}
+
+/// Writes made together or not at all, each recorded in the history of the game it changes as
+/// made by `author`.  Each write is conditional on what it replaces being as it was loaded, and
+/// one that finds someone else has saved it first cancels the lot with `DbError::Conflict`.
+///
+/// A game's version goes up whenever its hands change, as well as when it's saved, so a game
+/// saved as it was loaded (e.g. finished with the standings of the hands loaded with it) can't
+/// miss a hand saved since.  Hands can't be changed once their game is finished: that cancels
+/// the lot with `DbError::Locked`.
+pub struct Transaction {
+    author: String,
+    now: String,
+    writes: Vec<TransactWriteItem>,
+    /// The games being saved, with the tallies of the hands they were loaded with.
+    games_saved: Vec<(Game, HandTallies)>,
+    /// The games whose hands change, and how that changes their tallies.
+    games_touched: Vec<(String, HandTallies)>,
+    games_started: Vec<String>,
+}
+
+impl Transaction {
+    pub fn new(author: &str) -> Result<Transaction, DbError> {
+        Ok(Transaction {
+            author: author.to_string(),
+            now: timestamp(SystemTime::now())?,
+            writes: vec![],
+            games_saved: vec![],
+            games_touched: vec![],
+            games_started: vec![],
+        })
+    }
+
+    /// Saves a game, as long as no one else has saved it (or changed its hands) since it was
+    /// loaded at `game.version` with `hands`.  `before` is the game as it was, if it's not new.
+    pub fn put_game(&mut self, before: Option<&Game>, game: &Game, hands: &[CompletedHand]) -> Result<(), DbError> {
+        self.games_saved.push((game.clone(), HandTallies::of(game, hands)?));
+        self.record(&game.game_id, Change::Game { before: before.cloned(), after: Some(game.clone()) });
+        Ok(())
+    }
+
+    /// Marks a planned game as in progress, along with its first hand being added.
+    pub fn start_game(&mut self, game: &Game) {
+        self.games_started.push(game.game_id.clone());
+        let started = Game { status: GameStatus::InProgress, ..game.clone() };
+        self.record(&game.game_id, Change::Game { before: Some(game.clone()), after: Some(started) });
+    }
+
+    /// Saves a registry player.  Players aren't part of any one game's history.
+    pub fn put_player(&mut self, player: &Player) {
+        forget_registry();
+        self.writes.push(TransactWriteItem::builder()
+            .put(Put::builder()
+                .table_name((*TABLE_PLAYERS).clone())
+                .set_item(Some(player_item(player)))
+                .build())
+            .build());
+    }
+
+    /// Saves a new hand, whose players are referred to by name, storing them by player ID.
+    /// Refused if there's already a hand with the same ID.
+    pub fn add_hand(&mut self, game: &Game, hand: &CompletedHand) -> Result<(), DbError> {
+        let free = ("attribute_not_exists(handId)".to_string(), None);
+        self.writes.push(put_write(&TABLE_HANDS, hand_item(game, hand)?, free));
+        self.count(game, hand, 1)?;
+        self.record(&game.game_id, Change::Hand { before: None, after: Some(hand.clone()) });
+        Ok(())
+    }
+
+    /// Saves changes to a hand, `before` being the hand as it was, as long as no one else has
+    /// saved (or deleted) it since it was loaded at `hand.version`.
+    pub fn update_hand(&mut self, game: &Game, before: &CompletedHand, hand: &CompletedHand) -> Result<(), DbError> {
+        self.writes.push(put_write(&TABLE_HANDS, hand_item(game, hand)?, existing_hand_condition(hand.version)));
+        self.count(game, before, -1)?;
+        self.count(game, hand, 1)?;
+        self.record(&game.game_id, Change::Hand { before: Some(before.clone()), after: Some(hand.clone()) });
+        Ok(())
+    }
+
+    /// Moves a hand to a new hand ID (a new table or number): `before` is deleted, as long as no
+    /// one else has saved it since it was loaded at `hand.version`, and `hand` is saved under its
+    /// new ID, as long as that's free, or still holds `replacing` as it was loaded.
+    pub fn move_hand(&mut self, game: &Game, before: &CompletedHand, hand: &CompletedHand, replacing: Option<&CompletedHand>) -> Result<(), DbError> {
+        let condition = match replacing {
+            Some(replaced) => existing_hand_condition(replaced.version),
+            None => ("attribute_not_exists(handId)".to_string(), None),
+        };
+        self.writes.push(delete_write(&TABLE_HANDS, &game.game_id, &before.hand_id(), existing_hand_condition(hand.version)));
+        self.writes.push(put_write(&TABLE_HANDS, hand_item(game, hand)?, condition));
+        if let Some(replaced) = replacing {
+            self.count(game, replaced, -1)?;
+            self.record(&game.game_id, Change::Hand { before: Some(replaced.clone()), after: None });
+        }
+        self.count(game, before, -1)?;
+        self.count(game, hand, 1)?;
+        self.record(&game.game_id, Change::Hand { before: Some(before.clone()), after: Some(hand.clone()) });
+        Ok(())
+    }
+
+    /// Moves a hand to the trash, from which it can be restored until `HAND_TRASH_TTL` has passed,
+    /// as long as no one else has saved it since it was loaded at `hand.version`.  Returns the
+    /// hand as it is in the trash.
+    pub fn delete_hand(&mut self, game: &Game, hand: &CompletedHand) -> Result<CompletedHand, DbError> {
+        let deleted_at = SystemTime::now();
+        let deleted = CompletedHand { deleted_at: Some(timestamp(deleted_at)?), ..hand.clone() };
+        let mut item = hand_item(game, &deleted)?;
+        item.insert("handId".to_string(), to_s(&deleted.trash_id().unwrap_or_default()));
+        item.insert("expiresAt".to_string(), AttributeValue::N(DateTime::from(deleted_at + HAND_TRASH_TTL).secs().to_string()));
+        self.writes.push(delete_write(&TABLE_HANDS, &game.game_id, &hand.hand_id(), existing_hand_condition(hand.version)));
+        self.writes.push(put_write(&TABLE_HANDS, item, ("attribute_not_exists(handId)".to_string(), None)));
+        self.count(game, hand, -1)?;
+        self.record(&game.game_id, Change::Hand { before: Some(hand.clone()), after: None });
+        Ok(deleted)
+    }
+
+    /// Takes `hand` back out of the trash.  Refused if it's no longer there, or another hand has
+    /// been saved in its place since.
+    pub fn restore_hand(&mut self, game: &Game, hand: &CompletedHand) -> Result<(), DbError> {
+        let trash_id = hand.trash_id().ok_or(DbError::Validation(format!("Hand {} isn't in the trash", hand.hand_id())))?;
+        let restored = CompletedHand { deleted_at: None, ..hand.clone() };
+        self.writes.push(delete_write(&TABLE_HANDS, &game.game_id, &trash_id, ("attribute_exists(handId)".to_string(), None)));
+        self.writes.push(put_write(&TABLE_HANDS, hand_item(game, &restored)?, ("attribute_not_exists(handId)".to_string(), None)));
+        self.count(game, &restored, 1)?;
+        self.record(&game.game_id, Change::Hand { before: Some(hand.clone()), after: Some(restored) });
+        Ok(())
+    }
+
+    /// Moves hands between or within games, each hand's old item being replaced by its new one, so
+    /// that saved along with the games a hand is never under a game or table that doesn't have it.
+    /// Each move's games must be among `games`, and its hand as it was one of `hands`.  Refused if
+    /// a moving hand has been saved or deleted since it was loaded, or if its new key holds a hand
+    /// that isn't moving out.
+    pub fn move_hands(&mut self, games: &[&Game], moves: &[HandMove], hands: &[CompletedHand]) -> Result<(), DbError> {
+        // A key that one hand leaves and another takes is overwritten rather than deleted, since a
+        // transaction can only write each item once.
+        let taken: Vec<(&String, String)> = moves.iter().map(|m| (&m.to_game_id, m.hand.hand_id())).collect();
+        let game = |game_id: &String| games.iter()
+            .find(|g| g.game_id == *game_id)
+            .copied()
+            .ok_or(DbError::Validation(format!("Game {:?} isn't being saved", game_id)));
+
+        for hand_move in moves {
+            let (from_game, to_game) = (game(&hand_move.from_game_id)?, game(&hand_move.to_game_id)?);
+            if !taken.contains(&(&hand_move.from_game_id, hand_move.from_hand_id.clone())) {
+                let condition = existing_hand_condition(hand_move.hand.version);
+                self.writes.push(delete_write(&TABLE_HANDS, &hand_move.from_game_id, &hand_move.from_hand_id, condition));
+            }
+            // A key another hand is leaving must still hold that hand as it was loaded, and any
+            // other key must be free.
+            let hand_id = hand_move.hand.hand_id();
+            let leaving = moves.iter().find(|m| m.from_game_id == hand_move.to_game_id && m.from_hand_id == hand_id);
+            let condition = match leaving {
+                Some(m) => existing_hand_condition(m.hand.version),
+                None => ("attribute_not_exists(handId)".to_string(), None),
+            };
+            self.writes.push(put_write(&TABLE_HANDS, hand_item(to_game, &hand_move.hand)?, condition));
+
+            let before = hands.iter().find(|h| h.hand_id() == hand_move.from_hand_id).cloned();
+            if let Some(before) = &before {
+                self.count(from_game, before, -1)?;
+            }
+            self.count(to_game, &hand_move.hand, 1)?;
+            self.record(&hand_move.to_game_id, Change::Hand { before, after: Some(hand_move.hand.clone()) });
+        }
+        Ok(())
+    }
+
+    /// Counts a change to `game`'s hands in its tallies.
+    fn count(&mut self, game: &Game, hand: &CompletedHand, sign: i32) -> Result<(), DbError> {
+        match self.games_touched.iter_mut().find(|(g, _)| *g == game.game_id) {
+            Some((_, tallies)) => tallies.add(game, hand, sign),
+            None => {
+                let mut tallies = HandTallies::default();
+                tallies.add(game, hand, sign)?;
+                self.games_touched.push((game.game_id.clone(), tallies));
+                Ok(())
+            },
+        }
+    }
+
+    fn record(&mut self, game_id: &str, change: Change) {
+        let item = history_item(game_id, &self.author, &self.now, change);
+        self.writes.push(put_write(&TABLE_HISTORY, item, ("attribute_not_exists(entryId)".to_string(), None)));
+    }
+
+    /// Makes the writes.  It's all or nothing, so refused with `DbError::TooManyWrites` if they
+    /// don't fit in a single transaction.
+    pub async fn send(mut self, client: &Client) -> Result<(), DbError> {
+        // Games being saved are checked by their version, and saved with their new tallies.
+        let changes = |game_id: &String| self.games_touched.iter().find(|(g, _)| g == game_id).map(|(_, t)| t);
+        for (game, loaded) in &self.games_saved {
+            let mut tallies = loaded.clone();
+            if let Some(change) = changes(&game.game_id) {
+                tallies.merge(change);
+            }
+            self.writes.push(put_write(&TABLE_GAMES, tallied_game_item(game, &tallies), version_condition(game.version)));
+        }
+
+        // The others are checked, bumped and tallied here, as long as they're still there and
+        // not finished.
+        let touched: Vec<&(String, HandTallies)> = self.games_touched
+            .iter()
+            .filter(|(g, _)| !self.games_saved.iter().any(|(saved, _)| saved.game_id == *g))
+            .collect();
+        let first_touch = self.writes.len();
+        for (game_id, tallies) in &touched {
+            let mut update = Update::builder()
+                .table_name((*TABLE_GAMES).clone())
+                .key("gameId", to_s(game_id))
+                .condition_expression("attribute_exists(gameId) AND (attribute_not_exists(#status) OR #status <> :finished)")
+                .expression_attribute_names("#status", "status")
+                .expression_attribute_values(":zero", to_n(0))
+                .expression_attribute_values(":one", to_n(1))
+                .expression_attribute_values(":finished", AttributeValue::S(GameStatus::Finished.to_string()));
+            let mut set = "SET version = if_not_exists(version, :zero) + :one".to_string();
+            if self.games_started.contains(game_id) {
+                set.push_str(", #status = :inProgress");
+                update = update.expression_attribute_values(":inProgress", AttributeValue::S(GameStatus::InProgress.to_string()));
+            }
+            let mut adds = vec![];
+            for (i, (attribute, value)) in tallies.attributes().into_iter().enumerate() {
+                adds.push(format!("#tally{} :tally{}", i, i));
+                update = update
+                    .expression_attribute_names(format!("#tally{}", i), attribute)
+                    .expression_attribute_values(format!(":tally{}", i), value);
+            }
+            self.writes.push(TransactWriteItem::builder()
+                .update(update.update_expression(format!("{} ADD {}", set, adds.join(", "))).build())
+                .build());
+        }
+        if self.writes.len() > MAX_TRANSACTION_WRITES {
+            return Err(DbError::TooManyWrites(self.writes.len()));
+        }
+
+        let result = client
+            .transact_write_items()
+            .set_transact_items(Some(self.writes))
+            .send()
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                // A failed condition on a touched game's update means it was finished (or deleted) first.
+                let locked = failed_conditions(&e).into_iter().find_map(|i| i.checked_sub(first_touch).map(|t| touched[t].0.clone()));
+                match locked {
+                    Some(game_id) => Err(DbError::Locked(game_id)),
+                    None => Err(transaction_error(e, "these changes".to_string())),
+                }
+            },
+        }
+    }
+}
+
+fn put_write(table: &str, item: HashMap<String, AttributeValue>, (condition, values): (String, Option<HashMap<String, AttributeValue>>)) -> TransactWriteItem {
+    TransactWriteItem::builder()
+        .put(Put::builder()
+            .table_name(table)
+            .set_item(Some(item))
+            .condition_expression(condition)
+            .set_expression_attribute_values(values)
+            .build())
+        .build()
+}
+
+fn delete_write(table: &str, game_id: &str, hand_id: &str, (condition, values): (String, Option<HashMap<String, AttributeValue>>)) -> TransactWriteItem {
+    TransactWriteItem::builder()
+        .delete(Delete::builder()
+            .table_name(table)
+            .key("gameId", to_s(game_id))
+            .key("handId", to_s(hand_id))
+            .condition_expression(condition)
+            .set_expression_attribute_values(values)
+            .build())
+        .build()
+}
+
+/// The positions of the writes whose condition failed, if that's what cancelled a transaction.
+fn failed_conditions(e: &SdkError<TransactWriteItemsError>) -> Vec<usize> {
+    match service_error(e) {
+        Some(TransactWriteItemsError::TransactionCanceledException(err)) => err
+            .cancellation_reasons()
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+            .filter(|(_, reason)| reason.code() == Some("ConditionalCheckFailed"))
+            .map(|(i, _)| i)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Turns a transaction cancelled by a failed condition into a `DbError::Conflict` about `what`.
+fn transaction_error(e: SdkError<TransactWriteItemsError>, what: String) -> DbError {
+    let conflict = !failed_conditions(&e).is_empty();
+    if conflict {
+        DbError::Conflict(format!("Someone else just saved {}", what))
+    } else {
+        e.into()
+    }
+}
+
+/// How long pages that show the registry can go on using this Lambda instance's copy of it.
+const REGISTRY_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// The registry, as `get_players` last read it on this Lambda instance, if that was within
+/// `REGISTRY_CACHE_TTL`.  For pages that only show it, which can afford to be a little behind
+/// changes made on other instances: this instance's own changes are read again straight away.
+pub async fn get_players_cached(client: &Client) -> Result<Vec<Player>, DbError> {
+    if let Some((read_at, players)) = &*REGISTRY.lock().unwrap() {
+        if read_at.elapsed() < REGISTRY_CACHE_TTL {
+            return Ok(players.clone());
+        }
+    }
+    get_players(client).await
+}
+
+fn forget_registry() {
+    *REGISTRY.lock().unwrap() = None;
+}
+
+pub async fn get_players(client: &Client) -> Result<Vec<Player>, DbError> {
+    let mut players = vec![];
+    let mut exclusive_start_key = None;
+    loop {
+        let result = client
+            .scan()
+            .table_name((*TABLE_PLAYERS).clone())
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await?;
+        for item in result.items.unwrap_or_default() {
+            players.push(item_to_player(item)?);
+        }
+        exclusive_start_key = result.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            break;
+        }
+    }
+    players.sort_by_key(|p| p.display_name.to_lowercase());
+    *REGISTRY.lock().unwrap() = Some((Instant::now(), players.clone()));
+    Ok(players)
+}
+
+pub async fn get_player(client: &Client, player_id: &str) -> Result<Option<Player>, DbError> {
+    let result = client
+        .get_item()
+        .table_name((*TABLE_PLAYERS).clone())
+        .key("playerId", to_s(player_id))
+        .send()
+        .await?;
+
+    result.item.map(item_to_player).transpose()
+}
+
+pub async fn get_players_by_id(client: &Client, player_ids: &[String]) -> Result<HashMap<String, Player>, DbError> {
+    let mut players = HashMap::new();
+    // BatchGetItem takes at most 100 keys, and may not process all of them.
+    for chunk in player_ids.chunks(100) {
+        let mut keys: Vec<HashMap<String, AttributeValue>> = chunk
+            .iter()
+            .map(|id| HashMap::from([("playerId".to_string(), to_s(id))]))
+            .collect();
+        while !keys.is_empty() {
+            let result = client
+                .batch_get_item()
+                .request_items((*TABLE_PLAYERS).clone(), KeysAndAttributes::builder().set_keys(Some(keys)).build())
+                .send()
+                .await?;
+            for item in result.responses.and_then(|mut r| r.remove(&*TABLE_PLAYERS)).unwrap_or_default() {
+                let player = item_to_player(item)?;
+                players.insert(player.player_id.clone(), player);
+            }
+            keys = result.unprocessed_keys
+                .and_then(|mut k| k.remove(&*TABLE_PLAYERS))
+                .and_then(|k| k.keys)
+                .unwrap_or_default();
+        }
+    }
+    Ok(players)
+}
+
+pub async fn put_player(client: &Client, player: &Player) -> Result<(), DbError> {
+    forget_registry();
     client
         .put_item()
-        .table_name((*TABLE_GAMES).clone())
-        .item("gameId", to_s(&game.game_id))
-        .item("date", to_s(&game.date))
-        .item("host", to_s(&game.host))
-        .item("players", to_l_of_s(&game.players))
-        .item("tables", to_l_of_s(&game.tables))
+        .table_name((*TABLE_PLAYERS).clone())
+        .set_item(Some(player_item(player)))
         .send()
         .await?;
     Ok(())
 }
 
+fn player_item(player: &Player) -> HashMap<String, AttributeValue> {
+    HashMap::from([
+        ("playerId".to_string(), to_s(&player.player_id)),
+        ("displayName".to_string(), to_s(&player.display_name)),
+        ("aliases".to_string(), to_l_of_s(&player.aliases)),
+    ])
+}
+
+fn item_to_player(item: HashMap<String, AttributeValue>) -> Result<Player, DbError> {
+    Ok(Player {
+        player_id: get_s(&item, "playerId")?,
+        display_name: get_s(&item, "displayName")?,
+        aliases: get_l_of_s(&item, "aliases")?,
+    })
+}
+
+/// Applies `f` to every mention of a player in `hand`.
+fn map_hand_players<F>(hand: CompletedHand, f: F) -> Result<CompletedHand, DbError>
+where
+    F: Fn(String) -> Result<String, DbError>
+{
+    Ok(CompletedHand {
+        players: hand.players.into_iter().map(&f).collect::<Result<_, _>>()?,
+        bidder: f(hand.bidder)?,
+        partner: hand.partner.map(&f).transpose()?,
+        defence: hand.defence.into_iter().map(&f).collect::<Result<_, _>>()?,
+        ..hand
+    })
+}
+
 fn item_to_completed_hand(item: HashMap<String, AttributeValue>) -> Result<CompletedHand, DbError> {
     Ok(CompletedHand {
         table: get_s(&item, "table")?,
@@ -98,71 +897,419 @@ fn item_to_completed_hand(item: HashMap<String, AttributeValue>) -> Result<Compl
             get_s(&item, "chelem")?.as_str(),
         )
         .map_err(|e| DbError::Validation(format!("Invalid chelem {:?}", e.to_string())))?,
+        version: get_version(&item)?,
+        notes: get_option_s(&item, "notes")?.unwrap_or_default(),
+        disputed: item.contains_key("disputed") && get_bool(&item, "disputed")?,
+        deleted_at: get_option_s(&item, "deletedAt")?,
+        created_at: get_option_s(&item, "createdAt")?,
+        updated_at: get_option_s(&item, "updatedAt")?,
+        entered_by: get_option_s(&item, "enteredBy")?,
+        confirmed_by: get_option_s(&item, "confirmedBy")?,
+        entered_device: get_option_s(&item, "enteredDevice")?,
     })
 }
 
-pub async fn get_hands(client: &Client, game_id: &str) -> Result<Vec<CompletedHand>, DbError> {
-    let result = client
-        .query()
-        .table_name((*TABLE_HANDS).clone())
-        .key_condition_expression("gameId = :gameId")
-        .expression_attribute_values(":gameId", AttributeValue::S(game_id.to_string()))
-        .scan_index_forward(true)
-        .send()
-        .await?;
+/// A game's hands, with players referred to by name (see `Game::players`).  Hands in the trash
+/// are left out.
+pub async fn get_hands(client: &Client, game: &Game) -> Result<Vec<CompletedHand>, DbError> {
+    Ok(get_hands_and_trash(client, game).await?.0)
+}
 
-    let items = match result.items {
-        Some(items) => items,
-        None => return Ok(vec![]),
-    };
+/// The hands of a game that are in the trash, most recently deleted first.
+pub async fn get_deleted_hands(client: &Client, game: &Game) -> Result<Vec<CompletedHand>, DbError> {
+    Ok(get_hands_and_trash(client, game).await?.1)
+}
 
-    let hands: Result<Vec<CompletedHand>, _> = items
+/// A game's hands, and those in its trash (as `get_deleted_hands`), read together.  Hands in the
+/// trash whose players have since been removed from the game are left out, since they can't be
+/// restored to it.
+pub async fn get_hands_and_trash(client: &Client, game: &Game) -> Result<(Vec<CompletedHand>, Vec<CompletedHand>), DbError> {
+    let (trash, hands): (Vec<_>, Vec<_>) = get_hand_items(client, &game.game_id)
+        .await?
         .into_iter()
-        .map(|item| item_to_completed_hand(item))
-        .collect();
+        .partition(|item| item.contains_key("deletedAt"));
+    // A game part way through its migration to the registry has hands that already refer to
+    // players by ID, which need the registry to be read by name.
+    let half_migrated = game.player_ids.is_empty() && hands.iter().chain(trash.iter()).any(|item| {
+        get_l_of_s(item, "players").is_ok_and(|players| players.iter().any(|p| !game.players.contains(p)))
+    });
+    let registry = if half_migrated { get_players(client).await? } else { vec![] };
+    let hands = hands.into_iter().map(|item| item_to_game_hand(game, &registry, item)).collect::<Result<_, _>>()?;
+    let mut trash: Vec<CompletedHand> = trash.into_iter().filter_map(|item| item_to_game_hand(game, &registry, item).ok()).collect();
+    trash.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    Ok((hands, trash))
+}
 
-    hands
+/// All the items of a game's hands, including those in the trash.
+async fn get_hand_items(client: &Client, game_id: &str) -> Result<Vec<HashMap<String, AttributeValue>>, DbError> {
+    let mut items = vec![];
+    let mut exclusive_start_key = None;
+    loop {
+        let result = client
+            .query()
+            .table_name((*TABLE_HANDS).clone())
+            .key_condition_expression("gameId = :gameId")
+            .expression_attribute_values(":gameId", to_s(game_id))
+            .scan_index_forward(true)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await?;
+        items.extend(result.items.unwrap_or_default());
+        exclusive_start_key = result.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            break;
+        }
+    }
+    Ok(items)
 }
 
-pub async fn put_hand(client: &Client, game_id: &str, hand: &CompletedHand) -> Result<(), DbError> {
-    client
-        .put_item()
-        .table_name((*TABLE_HANDS).clone())
-        .item("gameId", to_s(&game_id.to_string()))
-        .item("handId", AttributeValue::S(hand.hand_id()))
-        .item("table", to_s(&hand.table))
-        .item("handNumber", to_n(hand.hand_number))
-        .item("players", to_l_of_s(&hand.players))
-        .item("bid", AttributeValue::S(hand.bid.to_string()))
-        .item("bidder", to_s(&hand.bidder))
-        .item(
-            "partner",
+/// Reads a hand of `game`, referring to its players by name.  `registry` is only needed for a
+/// game that hasn't been migrated to it, whose hands have been part way through.
+fn item_to_game_hand(game: &Game, registry: &[Player], item: HashMap<String, AttributeValue>) -> Result<CompletedHand, DbError> {
+    let hand = item_to_completed_hand(item)?;
+    if game.player_ids.is_empty() {
+        // The game still knows its players by the names they had before the migration.
+        map_hand_players(hand, |name| match registry.iter().find(|p| p.player_id == name) {
+            Some(p) if !game.players.contains(&name) => {
+                Ok(game.players.iter().find(|n| p.is_called(n)).unwrap_or(&p.display_name).clone())
+            },
+            _ => Ok(name),
+        })
+    } else {
+        map_hand_players(hand, |id| match game.player_name(&id) {
+            Some(name) => Ok(name.clone()),
+            // A hand left behind by a migration to the registry that stopped part way through
+            None if game.players.contains(&id) => Ok(id),
+            None => Err(DbError::Validation(format!("Unknown player {:?} in game {:?}", id, game.game_id))),
+        })
+    }
+}
+
+fn hand_item(game: &Game, hand: &CompletedHand) -> Result<HashMap<String, AttributeValue>, DbError> {
+    let hand = if game.player_ids.is_empty() {
+        hand.clone()
+    } else {
+        map_hand_players(hand.clone(), |name| match game.player_id(&name) {
+            Some(id) => Ok(id.clone()),
+            None => Err(DbError::Validation(format!("{:?} is not a player in game {:?}", name, game.game_id))),
+        })?
+    };
+
+    let mut item = hand_attributes(&hand);
+    item.insert("gameId".to_string(), to_s(&game.game_id));
+    // Kept out of history snapshots, which don't need to tell devices apart.
+    if let Some(device) = &hand.entered_device {
+        item.insert("enteredDevice".to_string(), to_s(device));
+    }
+    Ok(item)
+}
+
+/// A hand's attributes, other than the game it belongs to.
+fn hand_attributes(hand: &CompletedHand) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::from([
+        ("handId".to_string(), AttributeValue::S(hand.hand_id())),
+        ("table".to_string(), to_s(&hand.table)),
+        ("handNumber".to_string(), to_n(hand.hand_number)),
+        ("players".to_string(), to_l_of_s(&hand.players)),
+        ("bid".to_string(), AttributeValue::S(hand.bid.to_string())),
+        ("bidder".to_string(), to_s(&hand.bidder)),
+        (
+            "partner".to_string(),
             match &hand.partner {
                 Some(p) => to_s(p),
                 None => AttributeValue::Null(true),
             },
-        )
-        .item("defence", to_l_of_s(&hand.defence))
-        .item("won", AttributeValue::Bool(hand.won))
-        .item("wonOrLostBy", to_n(hand.won_or_lost_by))
-        .item("petitAuBout", AttributeValue::Bool(hand.petit_au_bout))
-        .item("poignee", AttributeValue::S(hand.poignee.to_string()))
-        .item("chelem", AttributeValue::S(hand.chelem.to_string()))
+        ),
+        ("defence".to_string(), to_l_of_s(&hand.defence)),
+        ("won".to_string(), AttributeValue::Bool(hand.won)),
+        ("wonOrLostBy".to_string(), to_n(hand.won_or_lost_by)),
+        ("petitAuBout".to_string(), AttributeValue::Bool(hand.petit_au_bout)),
+        ("poignee".to_string(), AttributeValue::S(hand.poignee.to_string())),
+        ("chelem".to_string(), AttributeValue::S(hand.chelem.to_string())),
+        ("notes".to_string(), to_s(&hand.notes)),
+        ("disputed".to_string(), AttributeValue::Bool(hand.disputed)),
+        ("version".to_string(), to_n(hand.version + 1)),
+    ]);
+    let optional = [
+        ("deletedAt", &hand.deleted_at),
+        ("createdAt", &hand.created_at),
+        ("updatedAt", &hand.updated_at),
+        ("enteredBy", &hand.entered_by),
+        ("confirmedBy", &hand.confirmed_by),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = value {
+            item.insert(key.to_string(), to_s(value));
+        }
+    }
+    item
+}
+
+/// The most writes DynamoDB accepts in one batch.
+const MAX_BATCH_WRITES: usize = 25;
+
+/// Deletes a game, all its hands (including those in the trash) and its history for good, as long
+/// as no one else has changed it since it was loaded at `game.version`.  It's all done in one
+/// transaction if it fits.  Otherwise the game goes first, along with as much as fits, and the rest
+/// is deleted in batches afterwards: it can't be reached without the game.
+pub async fn delete_game(client: &Client, game: &Game) -> Result<(), DbError> {
+    let mut keys = vec![];
+    for item in get_hand_items(client, &game.game_id).await? {
+        keys.push(((*TABLE_HANDS).clone(), HashMap::from([
+            ("gameId".to_string(), to_s(&game.game_id)),
+            ("handId".to_string(), to_s(&get_s(&item, "handId")?)),
+        ])));
+    }
+    for entry_id in get_history_entry_ids(client, &game.game_id).await? {
+        keys.push(((*TABLE_HISTORY).clone(), HashMap::from([
+            ("gameId".to_string(), to_s(&game.game_id)),
+            ("entryId".to_string(), to_s(&entry_id)),
+        ])));
+    }
+
+    let (condition, values) = version_condition(game.version);
+    let mut writes = vec![TransactWriteItem::builder()
+        .delete(Delete::builder()
+            .table_name((*TABLE_GAMES).clone())
+            .key("gameId", to_s(&game.game_id))
+            .condition_expression(condition)
+            .set_expression_attribute_values(values)
+            .build())
+        .build()];
+    let rest = keys.split_off(keys.len().min(MAX_TRANSACTION_WRITES - 1));
+    for (table, key) in keys {
+        writes.push(TransactWriteItem::builder()
+            .delete(Delete::builder().table_name(table).set_key(Some(key)).build())
+            .build());
+    }
+    client
+        .transact_write_items()
+        .set_transact_items(Some(writes))
         .send()
-        .await?;
+        .await
+        .map_err(|e| transaction_error(e, format!("game {:?}", game.game_id)))?;
 
+    // The game is gone, so whatever's left behind if this fails is only taking up space.
+    for batch in rest.chunks(MAX_BATCH_WRITES) {
+        if let Err(e) = delete_batch(client, batch).await {
+            tracing::error!("Game {:?} deleted, but not all its hands and history: {}", game.game_id, e);
+            break;
+        }
+    }
     Ok(())
 }
 
-pub async fn delete_hand(client: &Client, game_id: &str, hand_id: &str) -> Result<(), DbError> {
+/// Deletes the items with `keys`, each in its table, retrying any that DynamoDB leaves unprocessed.
+async fn delete_batch(client: &Client, keys: &[(String, HashMap<String, AttributeValue>)]) -> Result<(), DbError> {
+    let mut requests: HashMap<String, Vec<WriteRequest>> = HashMap::new();
+    for (table, key) in keys {
+        let request = WriteRequest::builder()
+            .delete_request(DeleteRequest::builder().set_key(Some(key.clone())).build())
+            .build();
+        requests.entry(table.clone()).or_default().push(request);
+    }
+    while !requests.is_empty() {
+        let result = client
+            .batch_write_item()
+            .set_request_items(Some(requests))
+            .send()
+            .await?;
+        requests = result.unprocessed_items.unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// The IDs of a game's history entries.
+async fn get_history_entry_ids(client: &Client, game_id: &str) -> Result<Vec<String>, DbError> {
+    let mut entry_ids = vec![];
+    let mut exclusive_start_key = None;
+    loop {
+        let result = client
+            .query()
+            .table_name((*TABLE_HISTORY).clone())
+            .key_condition_expression("gameId = :gameId")
+            .expression_attribute_values(":gameId", to_s(game_id))
+            .projection_expression("entryId")
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await?;
+        for item in result.items.unwrap_or_default() {
+            entry_ids.push(get_s(&item, "entryId")?);
+        }
+        exclusive_start_key = result.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            break;
+        }
+    }
+    Ok(entry_ids)
+}
+
+/// How long a deleted hand stays in the trash before DynamoDB deletes it for good.
+const HAND_TRASH_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The history entry recording `change` as made at `now` by `author`.
+fn history_item(game_id: &str, author: &str, now: &str, change: Change) -> HashMap<String, AttributeValue> {
+    let mut item = HashMap::from([
+        ("gameId".to_string(), to_s(game_id)),
+        ("entryId".to_string(), to_s(&format!("{}#{}", now, Uuid::new_v4()))),
+        ("timestamp".to_string(), to_s(now)),
+        ("author".to_string(), to_s(author)),
+    ]);
+    // Snapshots refer to players by name, so that they read the same whatever happens to the
+    // registry since.
+    let (kind, before, after) = match change {
+        Change::Hand { before, after } => ("hand", before.map(|h| hand_attributes(&h)), after.map(|h| hand_attributes(&h))),
+        Change::Game { before, after } => {
+            let snapshot = |g: Game| game_item(&Game { player_ids: vec![], ..g });
+            ("game", before.map(snapshot), after.map(snapshot))
+        },
+    };
+    item.insert("kind".to_string(), to_s(kind));
+    if let Some(before) = before {
+        item.insert("before".to_string(), AttributeValue::M(before));
+    }
+    if let Some(after) = after {
+        item.insert("after".to_string(), AttributeValue::M(after));
+    }
+    item
+}
+
+/// A game's history, most recent first.
+pub async fn get_history(client: &Client, game_id: &str) -> Result<Vec<HistoryEntry>, DbError> {
+    let mut entries = vec![];
+    let mut exclusive_start_key = None;
+    loop {
+        let result = client
+            .query()
+            .table_name((*TABLE_HISTORY).clone())
+            .key_condition_expression("gameId = :gameId")
+            .expression_attribute_values(":gameId", to_s(game_id))
+            .scan_index_forward(false)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await?;
+        for item in result.items.unwrap_or_default() {
+            entries.push(item_to_history_entry(item)?);
+        }
+        exclusive_start_key = result.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            break;
+        }
+    }
+    Ok(entries)
+}
+
+fn item_to_history_entry(item: HashMap<String, AttributeValue>) -> Result<HistoryEntry, DbError> {
+    let snapshot = |key: &str| match item.get(key) {
+        Some(AttributeValue::M(m)) => Ok(Some(m.clone())),
+        None => Ok(None),
+        Some(v) => Err(DbError::Validation(format!("Attribute {:?} is not a map: {:?}", key, v))),
+    };
+    let (before, after) = (snapshot("before")?, snapshot("after")?);
+    let change = match get_s(&item, "kind")?.as_str() {
+        "hand" => Change::Hand {
+            before: before.map(item_to_completed_hand).transpose()?,
+            after: after.map(item_to_completed_hand).transpose()?,
+        },
+        "game" => Change::Game {
+            before: before.map(|g| item_to_game(g, &HashMap::new())).transpose()?,
+            after: after.map(|g| item_to_game(g, &HashMap::new())).transpose()?,
+        },
+        kind => return Err(DbError::Validation(format!("Unknown history entry kind {:?}", kind))),
+    };
+
+    Ok(HistoryEntry {
+        entry_id: get_s(&item, "entryId")?,
+        timestamp: get_s(&item, "timestamp")?,
+        author: get_s(&item, "author")?,
+        change,
+    })
+}
+
+fn timestamp(t: SystemTime) -> Result<String, DbError> {
+    DateTime::from(t).fmt(Format::DateTime).map_err(|e| DbError::Validation(e.to_string()))
+}
+
+/// How long a form submission is remembered for.
+const SUBMISSION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a form submission can take to save.  A reservation older than this was left behind by
+/// a request that died before it could record or release it, and can be taken over.
+const SUBMISSION_PENDING_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// A form submission that has been made before.
+#[derive(Debug)]
+pub enum Submission {
+    /// Still being saved.
+    Pending,
+    /// Saved, and redirected to this location.
+    Redirected(String),
+}
+
+/// Reserves `token` for a form submission that's about to be saved, unless it's been submitted
+/// before, in which case that submission is returned instead.  DynamoDB deletes the reservation
+/// once its `expiresAt` has passed.
+pub async fn reserve_submission(client: &Client, token: &str) -> Result<Option<Submission>, DbError> {
+    loop {
+        let now = SystemTime::now();
+        let result = client
+            .put_item()
+            .table_name((*TABLE_SUBMISSIONS).clone())
+            .item("token", to_s(token))
+            .item("expiresAt", AttributeValue::N(DateTime::from(now + SUBMISSION_PENDING_TTL).secs().to_string()))
+            .condition_expression("attribute_not_exists(#token) OR (attribute_not_exists(#location) AND #expiresAt < :now)")
+            .expression_attribute_names("#token", "token")
+            .expression_attribute_names("#location", "location")
+            .expression_attribute_names("#expiresAt", "expiresAt")
+            .expression_attribute_values(":now", AttributeValue::N(DateTime::from(now).secs().to_string()))
+            .send()
+            .await;
+        match result {
+            Ok(_) => return Ok(None),
+            Err(e) if matches!(service_error(&e), Some(PutItemError::ConditionalCheckFailedException(_))) => {
+                let result = client
+                    .get_item()
+                    .table_name((*TABLE_SUBMISSIONS).clone())
+                    .key("token", to_s(token))
+                    .consistent_read(true)
+                    .send()
+                    .await?;
+                // Released in the meantime, so it's this submission's to reserve.
+                let Some(item) = result.item else { continue };
+                return Ok(Some(match get_option_s(&item, "location")? {
+                    Some(location) => Submission::Redirected(location),
+                    None => Submission::Pending,
+                }));
+            },
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Records where the form submission with `token`, as reserved by `reserve_submission`,
+/// redirected to.
+pub async fn put_submission(client: &Client, token: &str, location: &str) -> Result<(), DbError> {
+    let expires_at = SystemTime::now() + SUBMISSION_TTL;
     client
-        .delete_item()
-        .table_name((*TABLE_HANDS).clone())
-        .key("gameId", AttributeValue::S(game_id.to_string()))
-        .key("handId", AttributeValue::S(hand_id.to_string()))
+        .put_item()
+        .table_name((*TABLE_SUBMISSIONS).clone())
+        .item("token", to_s(token))
+        .item("location", to_s(location))
+        .item("expiresAt", AttributeValue::N(DateTime::from(expires_at).secs().to_string()))
         .send()
         .await?;
+    Ok(())
+}
 
+/// Releases `token`, reserved by a form submission that didn't save anything, so that the form
+/// can be submitted again.
+pub async fn delete_submission(client: &Client, token: &str) -> Result<(), DbError> {
+    client
+        .delete_item()
+        .table_name((*TABLE_SUBMISSIONS).clone())
+        .key("token", to_s(token))
+        .send()
+        .await?;
     Ok(())
 }
 
@@ -183,6 +1330,15 @@ fn get_option_s(
     }
 }
 
+/// An item's version, 0 for items saved before versions existed.
+fn get_version(item: &HashMap<String, AttributeValue>) -> Result<i32, DbError> {
+    if item.contains_key("version") {
+        get_n(item, "version")
+    } else {
+        Ok(0)
+    }
+}
+
 fn get_s(
     item: &std::collections::HashMap<String, AttributeValue>,
     key: &str,
@@ -281,14 +1437,14 @@ fn get_l_of_s(
 
 
 
-fn to_s(s: &String) -> AttributeValue {
-    AttributeValue::S(s.clone())
+fn to_s(s: &str) -> AttributeValue {
+    AttributeValue::S(s.to_string())
 }
 
 fn to_n(n: i32) -> AttributeValue {
     AttributeValue::N(n.to_string())
 }
 
-fn to_l_of_s(v: &Vec<String>) -> AttributeValue {
+fn to_l_of_s(v: &[String]) -> AttributeValue {
     AttributeValue::L(v.iter().map(|s| AttributeValue::S(s.clone())).collect())
 }
\ No newline at end of file
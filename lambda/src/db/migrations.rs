@@ -0,0 +1,169 @@
+//! One-off rewrites of stored data for changes to how it's stored.  They're run by hand, with the
+//! same environment as the Lambda, rather than on the way to serving a request:
+//!
+//! ```bash
+//! cargo run -- migrate
+//! ```
+//!
+//! Each one is idempotent: what's already been migrated is left alone, so running them again
+//! (e.g. after a run stopped part way through) finishes the job.
+
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::types::{AttributeValue, TransactWriteItem};
+use aws_sdk_dynamodb::Client;
+
+use super::*;
+use crate::game::resolve_players;
+
+pub async fn run(client: &Client) -> Result<(), DbError> {
+    let migrated = migrate_game_players(client).await?;
+    tracing::info!("Moved {} games to the player registry", migrated);
+    let tallied = tally_games(client).await?;
+    tracing::info!("Tallied the hands of {} games", tallied);
+    Ok(())
+}
+
+/// The items of `table` that match `filter`.
+async fn scan(client: &Client, table: &str, filter: &str) -> Result<Vec<HashMap<String, AttributeValue>>, DbError> {
+    let mut items = vec![];
+    let mut exclusive_start_key = None;
+    loop {
+        let result = client
+            .scan()
+            .table_name(table)
+            .filter_expression(filter)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await?;
+        items.extend(result.items.unwrap_or_default());
+        exclusive_start_key = result.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+/// Rewrites games saved before the player registry existed so that they, and their hands, refer
+/// to registry players by ID.  Names are matched against the registry's display names and
+/// aliases, and players are created for names it doesn't know.  Returns how many games were
+/// rewritten.
+async fn migrate_game_players(client: &Client) -> Result<usize, DbError> {
+    let games = scan(client, &TABLE_GAMES, "attribute_not_exists(playerIds)").await?;
+    for item in &games {
+        migrate_game(client, item_to_game(item.clone(), &HashMap::new())?).await?;
+    }
+    Ok(games.len())
+}
+
+async fn migrate_game(client: &Client, game: Game) -> Result<(), DbError> {
+    // Every hand, including those in the trash, as stored: the game isn't migrated yet, so
+    // nothing is mapped.
+    let items = get_hand_items(client, &game.game_id).await?;
+    let hands = items.iter().cloned().map(item_to_completed_hand).collect::<Result<Vec<_>, _>>()?;
+
+    // Hands rewritten by a run that stopped before saving the game already refer to players by
+    // ID, and those players have been created.
+    let registry = get_players(client).await?;
+    let by_id: HashMap<&String, &Player> = registry.iter().map(|p| (&p.player_id, p)).collect();
+
+    // Hands may still mention players who have since been removed from the game.
+    let mut names = game.players.clone();
+    for hand in &hands {
+        for player in &hand.players {
+            if !by_id.contains_key(player) && !names.contains(player) {
+                names.push(player.clone());
+            }
+        }
+    }
+    let (players, new_players) = resolve_players(&names, &registry);
+    let named: HashMap<&String, &Player> = names.iter().zip(players.iter()).collect();
+    let player = |name: &String| by_id.get(name).copied().or(named.get(name).copied());
+
+    // Two of the names may well be the same person under an alias.
+    let mut unique_players: Vec<&Player> = vec![];
+    let mentioned = hands.iter().flat_map(|h| h.players.iter());
+    for p in game.players.iter().chain(mentioned).filter_map(player) {
+        if !unique_players.iter().any(|u| u.player_id == p.player_id) {
+            unique_players.push(p);
+        }
+    }
+    let migrated = Game {
+        player_ids: unique_players.iter().map(|p| p.player_id.clone()).collect(),
+        players: unique_players.iter().map(|p| p.display_name.clone()).collect(),
+        ..game.clone()
+    };
+
+    let mut writes: Vec<TransactWriteItem> = new_players.iter()
+        .map(|p| TransactWriteItem::builder()
+            .put(Put::builder().table_name((*TABLE_PLAYERS).clone()).set_item(Some(player_item(p))).build())
+            .build())
+        .collect();
+    for (hand, stored) in hands.into_iter().zip(items.iter()) {
+        let version = hand.version;
+        let hand = map_hand_players(hand, |name| match player(&name) {
+            Some(p) => Ok(p.display_name.clone()),
+            None => Err(DbError::Validation(format!("Unknown player {:?} in game {:?}", name, game.game_id))),
+        })?;
+        let mut item = hand_item(&migrated, &hand)?;
+        // Keep the key and expiry of hands in the trash.
+        for key in ["handId", "expiresAt"] {
+            if let Some(value) = stored.get(key) {
+                item.insert(key.to_string(), value.clone());
+            }
+        }
+        let (condition, values) = version_condition(version);
+        writes.push(put_write(&TABLE_HANDS, item, (format!("attribute_exists(handId) AND {}", condition), values)));
+    }
+    writes.push(put_write(&TABLE_GAMES, game_item(&migrated), version_condition(game.version)));
+
+    if writes.len() <= MAX_TRANSACTION_WRITES {
+        client
+            .transact_write_items()
+            .set_transact_items(Some(writes))
+            .send()
+            .await
+            .map_err(|e| transaction_error(e, format!("game {:?}", game.game_id)))?;
+    } else {
+        // Too big to do at once: the players go first, and the game last, so that a run that
+        // stops part way through leaves the game to be migrated again.  In the meantime, hands
+        // already rewritten are read by name through the registry (see `item_to_game_hand`).
+        tracing::info!("Migrating game {:?} in {} writes", game.game_id, writes.len());
+        for write in writes {
+            client
+                .transact_write_items()
+                .transact_items(write)
+                .send()
+                .await
+                .map_err(|e| transaction_error(e, format!("game {:?}", game.game_id)))?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves games again with what listing them relies on, for games saved before it existed: the
+/// keys they're listed and searched by, and the tallies of their hands.  Returns how many games
+/// were saved.
+async fn tally_games(client: &Client) -> Result<usize, DbError> {
+    let games = scan(client, &TABLE_GAMES, "attribute_not_exists(tallied) OR attribute_not_exists(hostKey)").await?;
+    for item in &games {
+        let game_id = get_s(item, "gameId")?;
+        // A hand saved in the meantime changes the game's version: it's then tallied again.
+        while let Some(game) = get_game(client, &game_id).await? {
+            let hands = get_hands(client, &game).await?;
+            let item = tallied_game_item(&game, &HandTallies::of(&game, &hands)?);
+            let result = client
+                .transact_write_items()
+                .transact_items(put_write(&TABLE_GAMES, item, version_condition(game.version)))
+                .send()
+                .await;
+            match result.map_err(|e| transaction_error(e, format!("game {:?}", game_id))) {
+                Ok(_) => break,
+                Err(DbError::Conflict(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(games.len())
+}
@@ -0,0 +1,79 @@
+use crate::game::CompletedHand;
+
+/// How soon after one another two hands must have been entered to be taken for duplicates.
+const DUPLICATE_WINDOW_SECS: i64 = 10 * 60;
+
+/// Whether `a` and `b` look like the same physical hand entered twice: at the same table, with the
+/// same players, contract and result, under consecutive hand numbers, and (unless either predates
+/// timestamps) entered within a few minutes of each other.
+pub fn is_likely_duplicate(a: &CompletedHand, b: &CompletedHand) -> bool {
+    let entered_together = match (a.created_secs(), b.created_secs()) {
+        (Some(a), Some(b)) => (a - b).abs() <= DUPLICATE_WINDOW_SECS,
+        _ => true,
+    };
+
+    let mut a_players = a.players.clone();
+    let mut b_players = b.players.clone();
+    a_players.sort();
+    b_players.sort();
+
+    entered_together
+        && a.table == b.table
+        && (a.hand_number - b.hand_number).abs() == 1
+        && a_players == b_players
+        && a.bid == b.bid
+        && a.bidder == b.bidder
+        && a.partner == b.partner
+        && a.won == b.won
+        && a.won_or_lost_by == b.won_or_lost_by
+        && a.petit_au_bout == b.petit_au_bout
+        && a.poignee == b.poignee
+        && a.chelem == b.chelem
+}
+
+/// The pairs of `hands` that are likely duplicates, earlier hand first.
+pub fn find_duplicates<'a>(hands: &[&'a CompletedHand]) -> Vec<(&'a CompletedHand, &'a CompletedHand)> {
+    let mut duplicates = vec![];
+    for (i, a) in hands.iter().enumerate() {
+        for b in &hands[i + 1..] {
+            if is_likely_duplicate(a, b) {
+                if a.hand_number < b.hand_number {
+                    duplicates.push((*a, *b));
+                } else {
+                    duplicates.push((*b, *a));
+                }
+            }
+        }
+    }
+    duplicates.sort_by_key(|(a, _)| (a.table.clone(), a.hand_number));
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Bid;
+
+    fn hand(hand_number: i32, bidder: &str, won_or_lost_by: i32) -> CompletedHand {
+        let players = ["Alice", "Bob", "Charlie", "David"];
+        CompletedHand { hand_number, bid: Bid::Garde, won_or_lost_by, ..CompletedHand::test(&players, bidder, None) }
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let hands = [hand(1, "Alice", 5), hand(3, "Alice", 5), hand(2, "Alice", 5), hand(4, "Bob", 5), hand(5, "Bob", 6)];
+        let refs: Vec<&CompletedHand> = hands.iter().collect();
+        let duplicates: Vec<(i32, i32)> = find_duplicates(&refs).iter().map(|(a, b)| (a.hand_number, b.hand_number)).collect();
+        // 1 and 3 aren't consecutive, and 4 and 5 have different results.
+        assert_eq!(duplicates, vec![(1, 2), (2, 3)]);
+    }
+
+    #[test]
+    fn test_duplicates_entered_apart() {
+        let entered_at = |hand_number: i32, created_at: &str| CompletedHand { created_at: Some(created_at.to_string()), ..hand(hand_number, "Alice", 5) };
+        let soon_after = [entered_at(1, "2025-03-14T21:00:00Z"), entered_at(2, "2025-03-14T21:04:00Z")];
+        let much_later = [entered_at(1, "2025-03-14T21:00:00Z"), entered_at(2, "2025-03-14T21:30:00Z")];
+        assert!(is_likely_duplicate(&soon_after[0], &soon_after[1]));
+        assert!(!is_likely_duplicate(&much_later[0], &much_later[1]));
+    }
+}
@@ -1,6 +1,9 @@
 use std::fmt;
 use std::str::FromStr;
 
+use crate::local_time::parse_timestamp;
+use crate::names::{name_key, same_name};
+
 #[derive(Debug)]
 pub struct ValidationError{
     pub msg: String
@@ -109,13 +112,254 @@ impl FromStr for Chelem {
     }
 }
 
+/// How players are ranked in a game's summary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RankingMethod {
+    /// Sum of all scores.
+    Total,
+    /// Total divided by the number of hands played.
+    AveragePerHand,
+    /// Average per hand, with each hand's score scaled by the size of its table.
+    WeightedByTableSize,
+}
+
+impl fmt::Display for RankingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RankingMethod::Total => write!(f, "total"),
+            RankingMethod::AveragePerHand => write!(f, "moyenne par partie"),
+            RankingMethod::WeightedByTableSize => write!(f, "moyenne pondérée par table"),
+        }
+    }
+}
+
+impl FromStr for RankingMethod {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "total" => Ok(RankingMethod::Total),
+            "moyenne par partie" => Ok(RankingMethod::AveragePerHand),
+            "moyenne pondérée par table" => Ok(RankingMethod::WeightedByTableSize),
+            _ => Err(ValidationError { msg: s.to_string() }),
+        }
+    }
+}
+
+/// Whether a game's hands need confirming by a second player, and what happens to them until then.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Confirmation {
+    /// Hands count as soon as they're entered.
+    Off,
+    /// Hands are marked as pending until confirmed, but count straight away.
+    Counted,
+    /// Hands are left out of the totals until confirmed.
+    Excluded,
+}
+
+impl fmt::Display for Confirmation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Confirmation::Off => write!(f, "aucune"),
+            Confirmation::Counted => write!(f, "comptées en attendant"),
+            Confirmation::Excluded => write!(f, "pas comptées en attendant"),
+        }
+    }
+}
+
+impl FromStr for Confirmation {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "aucune" => Ok(Confirmation::Off),
+            "comptées en attendant" => Ok(Confirmation::Counted),
+            "pas comptées en attendant" => Ok(Confirmation::Excluded),
+            _ => Err(ValidationError { msg: s.to_string() }),
+        }
+    }
+}
+
+/// Where a game is in its life: hands can be entered until it's finished, and then it's locked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameStatus {
+    /// Created ahead of the evening: its first hand starts it.
+    Planned,
+    InProgress,
+    /// Its standings are frozen and nothing about it can be changed until it's reopened.
+    Finished,
+}
+
+impl fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameStatus::Planned => write!(f, "prévu"),
+            GameStatus::InProgress => write!(f, "en cours"),
+            GameStatus::Finished => write!(f, "terminé"),
+        }
+    }
+}
+
+impl FromStr for GameStatus {
+    type Err = ValidationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "prévu" => Ok(GameStatus::Planned),
+            "en cours" => Ok(GameStatus::InProgress),
+            "terminé" => Ok(GameStatus::Finished),
+            _ => Err(ValidationError { msg: s.to_string() }),
+        }
+    }
+}
+
+/// A person in the registry shared by every game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    pub player_id: String,
+    pub display_name: String,
+    pub aliases: Vec<String>,
+}
+
+impl Player {
+    pub fn new(display_name: &str) -> Player {
+        Player {
+            player_id: uuid::Uuid::new_v4().to_string(),
+            display_name: display_name.to_string(),
+            aliases: vec![],
+        }
+    }
+
+    /// Whether `name` is this player's display name or one of their aliases, give or take case,
+    /// accents and spacing.
+    pub fn is_called(&self, name: &str) -> bool {
+        std::iter::once(&self.display_name)
+            .chain(self.aliases.iter())
+            .any(|n| same_name(n, name))
+    }
+
+    /// Whether any of this player's names contains `query`, as `is_called` compares them.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = name_key(query);
+        std::iter::once(&self.display_name)
+            .chain(self.aliases.iter())
+            .any(|n| name_key(n).contains(&query))
+    }
+}
+
+/// Finds each of `names` in the registry, creating players for names nobody in it is called.
+///
+/// Returns the players for `names`, in order, and the new players among them that still need
+/// saving to the registry.
+pub fn resolve_players(names: &[String], registry: &[Player]) -> (Vec<Player>, Vec<Player>) {
+    let mut new_players: Vec<Player> = vec![];
+    let players = names
+        .iter()
+        .map(|name| {
+            if let Some(p) = registry.iter().chain(new_players.iter()).find(|p| p.is_called(name)) {
+                p.clone()
+            } else {
+                let player = Player::new(name);
+                new_players.push(player.clone());
+                player
+            }
+        })
+        .collect();
+    (players, new_players)
+}
+
+/// What games are being searched for, as typed into the search form.  Empty fields match
+/// everything, and dates are inclusive.
+#[derive(Debug, Clone, Default)]
+pub struct GameSearch {
+    pub host: String,
+    pub player: String,
+    pub from: String,
+    pub to: String,
+}
+
+impl GameSearch {
+    pub fn is_empty(&self) -> bool {
+        self.host.is_empty() && self.player.is_empty() && self.from.is_empty() && self.to.is_empty()
+    }
+
+    /// The search as query string parameters, for links to further pages of results.
+    pub fn to_query(&self) -> Vec<(&str, &str)> {
+        [("host", &self.host), ("player", &self.player), ("from", &self.from), ("to", &self.to)]
+            .into_iter()
+            .filter(|(_, v)| !v.is_empty())
+            .map(|(k, v)| (k, v.as_str()))
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Game {
     pub game_id: String,
     pub date: String,
     pub host: String,
+    /// The players' display names, which is how hands and scores refer to them.
     pub players: Vec<String>,
+    /// The registry IDs of `players`, in the same order.  Empty for games saved before the
+    /// registry existed, until they're migrated.
+    pub player_ids: Vec<String>,
     pub tables: Vec<String>,
+    pub ranking_method: RankingMethod,
+    pub confirmation: Confirmation,
+    pub status: GameStatus,
+    /// The ranking as it stood when the game was finished, best first.  Empty unless it's finished.
+    pub final_standings: Vec<(String, f64)>,
+    /// Archived games are left out of listings, but can still be opened by URL.
+    pub archived: bool,
+    /// How many times the game has been saved, so that saving it over someone else's changes can
+    /// be refused.  0 for a game that hasn't been saved yet.
+    pub version: i32,
+}
+
+impl Game {
+    pub fn player_id(&self, name: &str) -> Option<&String> {
+        self.players.iter().position(|p| p == name).and_then(|i| self.player_ids.get(i))
+    }
+
+    pub fn player_name(&self, player_id: &str) -> Option<&String> {
+        self.player_ids.iter().position(|id| id == player_id).and_then(|i| self.players.get(i))
+    }
+
+    /// Whether the game was saved before the registry existed, and hasn't been migrated to it yet.
+    pub fn needs_migration(&self) -> bool {
+        self.player_ids.len() != self.players.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status == GameStatus::Finished
+    }
+
+    /// Why the game can't be finished with `hands` as they stand, if it can't: every hand must be
+    /// settled first, so that the standings it's frozen with are final.
+    pub fn finish_blocker(&self, hands: &[CompletedHand]) -> Option<String> {
+        let describe = |hands: Vec<&CompletedHand>| {
+            hands.iter().map(|h| format!("#{} at table {}", h.hand_number, h.table)).collect::<Vec<_>>().join(", ")
+        };
+        let disputed: Vec<&CompletedHand> = hands.iter().filter(|h| h.disputed).collect();
+        if !disputed.is_empty() {
+            return Some(format!("Some hands are disputed: {}. Settle them before finishing the game", describe(disputed)));
+        }
+        let pending: Vec<&CompletedHand> = hands.iter().filter(|h| self.is_pending(h)).collect();
+        if !pending.is_empty() {
+            return Some(format!("Some hands haven't been confirmed: {}. Confirm them before finishing the game", describe(pending)));
+        }
+        None
+    }
+
+    /// Whether `hand` is still waiting for a second player to confirm it.
+    pub fn is_pending(&self, hand: &CompletedHand) -> bool {
+        self.confirmation != Confirmation::Off && hand.confirmed_by.is_none()
+    }
+
+    /// Whether `hand` counts towards the totals yet.
+    pub fn counts(&self, hand: &CompletedHand) -> bool {
+        self.confirmation != Confirmation::Excluded || hand.confirmed_by.is_some()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -132,6 +376,60 @@ pub struct CompletedHand {
     pub petit_au_bout: bool,
     pub poignee: Poignée,
     pub chelem: Chelem,
+    /// Anything worth remembering about the hand, e.g. what needs checking again.
+    pub notes: String,
+    /// Whether someone disputes the hand, until the dispute is settled.
+    pub disputed: bool,
+    /// As for `Game::version`: the version this hand was loaded at, or 0 for a new hand.
+    pub version: i32,
+    /// When the hand was moved to the trash, if it has been.
+    pub deleted_at: Option<String>,
+    /// When the hand was first saved, and last changed: unknown for hands saved before these were
+    /// recorded.
+    pub created_at: Option<String>,
+    pub updated_at: Option<String>,
+    /// Who entered the hand, as recorded in the game's history, and which of its players confirmed
+    /// it, if anyone has.
+    pub entered_by: Option<String>,
+    pub confirmed_by: Option<String>,
+    /// The device the hand was entered on (see `main::device_id`), which can't also confirm it.
+    pub entered_device: Option<String>,
+}
+
+#[cfg(test)]
+impl CompletedHand {
+    /// A hand for tests: hand #1 at table Atout, where `bidder` (with `partner`, if any) takes a
+    /// petite against the rest of `players` and makes it exactly.  Tests set whatever else matters
+    /// to them with struct update syntax.
+    pub fn test<S: AsRef<str>>(players: &[S], bidder: &str, partner: Option<&str>) -> CompletedHand {
+        CompletedHand {
+            table: "Atout".to_string(),
+            hand_number: 1,
+            players: players.iter().map(|p| p.as_ref().to_string()).collect(),
+            bid: Bid::Petite,
+            bidder: bidder.to_string(),
+            partner: partner.map(|p| p.to_string()),
+            defence: players.iter()
+                .map(|p| p.as_ref())
+                .filter(|p| *p != bidder && Some(*p) != partner)
+                .map(|p| p.to_string())
+                .collect(),
+            won: true,
+            won_or_lost_by: 0,
+            petit_au_bout: false,
+            poignee: Poignée::Aucune,
+            chelem: Chelem::Aucun,
+            notes: String::new(),
+            disputed: false,
+            version: 0,
+            deleted_at: None,
+            created_at: None,
+            updated_at: None,
+            entered_by: None,
+            confirmed_by: None,
+            entered_device: None,
+        }
+    }
 }
 
 pub fn hand_id(hand_number: i32, table: &str) -> String {
@@ -150,4 +448,15 @@ impl CompletedHand {
     pub fn hand_id(&self) -> String {
         hand_id(self.hand_number, &self.table)
     }
+
+    /// The ID a hand in the trash is kept under, out of the way of a hand saved in its place.
+    pub fn trash_id(&self) -> Option<String> {
+        self.deleted_at.as_ref().map(|deleted_at| format!("trash#{}#{}", deleted_at, self.hand_id()))
+    }
+
+    /// `created_at`, in seconds since the epoch.
+    pub fn created_secs(&self) -> Option<i64> {
+        self.created_at.as_deref()
+            .and_then(parse_timestamp)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,221 @@
+use crate::game::{CompletedHand, Game, GameStatus};
+
+/// A change to one of a game's hands, or to the game's settings: `before` is `None` for what's
+/// just been created, and `after` for what's just been deleted.
+// Changes are only made a handful at a time, so there's nothing to gain by boxing the hands.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone)]
+pub enum Change {
+    Hand { before: Option<CompletedHand>, after: Option<CompletedHand> },
+    Game { before: Option<Game>, after: Option<Game> },
+}
+
+/// An entry in a game's history, which is only ever added to.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    /// The timestamp, then something unique, so that entries sort in the order they were made.
+    pub entry_id: String,
+    pub timestamp: String,
+    /// Who made the change: the name they gave, or else the device they made it on.
+    pub author: String,
+    pub change: Change,
+}
+
+/// A field that a change set, cleared or changed.
+#[derive(Debug, PartialEq)]
+pub struct FieldChange {
+    pub label: &'static str,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl Change {
+    /// What changed, in a few words.
+    pub fn description(&self) -> String {
+        match self {
+            Change::Hand { before, after } => {
+                let hand = after.as_ref().or(before.as_ref());
+                let subject = hand.map(|h| format!("Partie #{} de la table {}", h.hand_number, h.table)).unwrap_or_default();
+                let verb = match (before, after) {
+                    (None, _) => "ajoutée",
+                    (_, None) => "supprimée",
+                    (Some(b), Some(_)) if b.deleted_at.is_some() => "restaurée",
+                    (Some(b), Some(a)) if b.confirmed_by.is_none() && a.confirmed_by.is_some() => "confirmée",
+                    (Some(_), Some(_)) => "modifiée",
+                };
+                format!("{} {}", subject, verb)
+            },
+            Change::Game { before, after } => match (before, after) {
+                (None, _) => "Jeu créé".to_string(),
+                (_, None) => "Jeu supprimé".to_string(),
+                (Some(b), Some(a)) if b.status != a.status => match a.status {
+                    GameStatus::InProgress if b.status == GameStatus::Finished => "Jeu rouvert".to_string(),
+                    GameStatus::InProgress => "Jeu commencé".to_string(),
+                    GameStatus::Finished => "Jeu terminé".to_string(),
+                    GameStatus::Planned => "Jeu modifié".to_string(),
+                },
+                (Some(_), Some(_)) => "Jeu modifié".to_string(),
+            },
+        }
+    }
+
+    /// The fields that differ between before and after.
+    pub fn diff(&self) -> Vec<FieldChange> {
+        match self {
+            Change::Hand { before, after } => diff_fields(before.as_ref().map(hand_fields), after.as_ref().map(hand_fields)),
+            Change::Game { before, after } => diff_fields(before.as_ref().map(game_fields), after.as_ref().map(game_fields)),
+        }
+    }
+}
+
+fn hand_fields(hand: &CompletedHand) -> Vec<(&'static str, String)> {
+    vec![
+        ("Table", hand.table.clone()),
+        ("Numéro", hand.hand_number.to_string()),
+        ("Joueurs", hand.players.join(", ")),
+        ("Contrat", hand.bid.to_string()),
+        ("Preneur", hand.bidder.clone()),
+        ("Partenaire", hand.partner.clone().unwrap_or_default()),
+        ("Défense", hand.defence.join(", ")),
+        ("Résultat", (if hand.won { "gagnée" } else { "chutée" }).to_string()),
+        ("De", hand.won_or_lost_by.to_string()),
+        ("Petit au bout", (if hand.petit_au_bout { "oui" } else { "non" }).to_string()),
+        ("Poignée", hand.poignee.to_string()),
+        ("Chelem", hand.chelem.to_string()),
+        ("Notes", hand.notes.clone()),
+        ("Contestée", (if hand.disputed { "oui" } else { "non" }).to_string()),
+        ("Confirmée par", hand.confirmed_by.clone().unwrap_or_default()),
+    ]
+}
+
+fn game_fields(game: &Game) -> Vec<(&'static str, String)> {
+    vec![
+        ("Date", game.date.clone()),
+        ("Chez", game.host.clone()),
+        ("Joueurs", game.players.join(", ")),
+        ("Tables", game.tables.join(", ")),
+        ("Classement", game.ranking_method.to_string()),
+        ("Confirmation", game.confirmation.to_string()),
+        ("État", game.status.to_string()),
+        ("Classement final", game.final_standings.iter().map(|(player, score)| format!("{} ({})", player, score)).collect::<Vec<_>>().join(", ")),
+        ("Archivé", (if game.archived { "oui" } else { "non" }).to_string()),
+    ]
+}
+
+fn diff_fields(before: Option<Vec<(&'static str, String)>>, after: Option<Vec<(&'static str, String)>>) -> Vec<FieldChange> {
+    let labels: Vec<&'static str> = before.iter().chain(after.iter()).flatten().map(|(label, _)| *label).collect();
+    let value = |fields: &Option<Vec<(&'static str, String)>>, label: &str| {
+        fields.as_ref()
+            .and_then(|f| f.iter().find(|(l, _)| *l == label))
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty())
+    };
+
+    let mut changes: Vec<FieldChange> = vec![];
+    for label in labels {
+        if changes.iter().any(|c| c.label == label) {
+            continue;
+        }
+        let (b, a) = (value(&before, label), value(&after, label));
+        if b != a {
+            changes.push(FieldChange { label, before: b, after: a });
+        }
+    }
+    changes
+}
+
+/// Who to record a change as made by: the name given on the form if there is one, or else the
+/// kind of device it was sent from.
+pub fn author_label(name: Option<&str>, user_agent: Option<&str>) -> String {
+    if let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) {
+        return name.to_string();
+    }
+    let user_agent = user_agent.unwrap_or_default();
+    let device = [
+        ("iPhone", "iPhone"),
+        ("iPad", "iPad"),
+        ("Android", "Android"),
+        ("Macintosh", "Mac"),
+        ("Windows", "Windows"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(pattern, _)| user_agent.contains(pattern))
+    .map(|(_, device)| *device);
+    match device {
+        Some(device) => format!("Appareil {}", device),
+        None => "Appareil inconnu".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Bid, Confirmation, RankingMethod};
+
+    fn hand(hand_number: i32, won_or_lost_by: i32) -> CompletedHand {
+        let players = ["Alice", "Bob", "Charlie", "David"];
+        CompletedHand { hand_number, bid: Bid::Garde, won_or_lost_by, ..CompletedHand::test(&players, "Alice", None) }
+    }
+
+    #[test]
+    fn test_hand_diff() {
+        let change = Change::Hand { before: Some(hand(3, 5)), after: Some(hand(4, 12)) };
+        assert_eq!(change.description(), "Partie #4 de la table Atout modifiée");
+        assert_eq!(change.diff(), vec![
+            FieldChange { label: "Numéro", before: Some("3".to_string()), after: Some("4".to_string()) },
+            FieldChange { label: "De", before: Some("5".to_string()), after: Some("12".to_string()) },
+        ]);
+
+        let deleted = Change::Hand { before: Some(hand(3, 5)), after: None };
+        assert_eq!(deleted.description(), "Partie #3 de la table Atout supprimée");
+        // Every field but the empty partner, notes and confirmation is cleared.
+        assert_eq!(deleted.diff().len(), 12);
+        assert!(deleted.diff().iter().all(|c| c.after.is_none()));
+    }
+
+    #[test]
+    fn test_confirmation_diff() {
+        let confirmed = CompletedHand { confirmed_by: Some("Bob".to_string()), ..hand(3, 5) };
+        let change = Change::Hand { before: Some(hand(3, 5)), after: Some(confirmed) };
+        assert_eq!(change.description(), "Partie #3 de la table Atout confirmée");
+        assert_eq!(change.diff(), vec![FieldChange { label: "Confirmée par", before: None, after: Some("Bob".to_string()) }]);
+    }
+
+    #[test]
+    fn test_finish_and_reopen() {
+        let game = Game {
+            game_id: "game".to_string(),
+            date: "2025-03-14".to_string(),
+            host: "Marie".to_string(),
+            players: vec!["Alice".to_string(), "Bob".to_string()],
+            player_ids: vec![],
+            tables: vec!["Atout".to_string()],
+            ranking_method: RankingMethod::Total,
+            confirmation: Confirmation::Off,
+            status: GameStatus::InProgress,
+            final_standings: vec![],
+            archived: false,
+            version: 3,
+        };
+        let finished = Game { status: GameStatus::Finished, final_standings: vec![("Alice".to_string(), 120.0), ("Bob".to_string(), -120.0)], ..game.clone() };
+
+        let finish = Change::Game { before: Some(game.clone()), after: Some(finished.clone()) };
+        assert_eq!(finish.description(), "Jeu terminé");
+        assert_eq!(finish.diff(), vec![
+            FieldChange { label: "État", before: Some("en cours".to_string()), after: Some("terminé".to_string()) },
+            FieldChange { label: "Classement final", before: None, after: Some("Alice (120), Bob (-120)".to_string()) },
+        ]);
+
+        let reopen = Change::Game { before: Some(finished), after: Some(game) };
+        assert_eq!(reopen.description(), "Jeu rouvert");
+    }
+
+    #[test]
+    fn test_author_label() {
+        assert_eq!(author_label(Some(" Marie "), Some("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0)")), "Marie");
+        assert_eq!(author_label(Some(""), Some("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0)")), "Appareil iPhone");
+        assert_eq!(author_label(None, Some("Mozilla/5.0 (Linux; Android 14)")), "Appareil Android");
+        assert_eq!(author_label(None, None), "Appareil inconnu");
+    }
+}
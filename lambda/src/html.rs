@@ -2,16 +2,20 @@ use std::{cmp::max, collections::HashMap};
 
 use http::Method;
 use maud::{html, Markup, DOCTYPE};
-use crate::{game::{hand_id, Bid, Chelem, CompletedHand, Game, Poignée}, server::routes::{url_for, Route}};
+use uuid::Uuid;
+use crate::{chart::score_chart, duplicates::find_duplicates, game::{hand_id, Bid, Chelem, CompletedHand, Confirmation, Game, GameSearch, GameStatus, Player, Poignée, RankingMethod}, history::HistoryEntry, local_time::format_timestamp, names::same_name, rearrange::renumber_table, stats::{PlayerStats, SessionTiming}, scoring::{GameListing, GameSummary, LeaderboardEntry}, server::routes::{url_for, Route}};
 
 const ADD_ICON: &str = "/assets/add_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const CLOSE_ICON: &str = "/assets/close_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
+const CONTENT_COPY_ICON: &str = "/assets/content_copy_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const DELETE_ICON: &str = "/assets/delete_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const EDIT_ICON: &str = "/assets/edit_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const GROUPS_ICON: &str = "/assets/groups_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const PLAYING_CARDS_ICON: &str = "/assets/playing_cards_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const QRCODE_ICON: &str = "/assets/qr_code_scanner_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
+const BAR_CHART_ICON: &str = "/assets/bar_chart_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 const SCOREBOARD_ICON: &str = "/assets/scoreboard_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
+const SHOW_CHART_ICON: &str = "/assets/show_chart_24dp_1F1F1F_FILL0_wght400_GRAD0_opsz24.svg";
 
 fn layout(content: Markup) -> Markup {
     let script_file = std::env::var("SCRIPT_JS").unwrap_or("script.js".to_string());
@@ -61,22 +65,156 @@ fn player_select(
     }
 }
 
-pub fn html_new_or_edit_game(game: Option<&Game>) -> Markup {
+pub fn html_new_or_edit_game(game: Option<&Game>, registry: &[Player]) -> Markup {
+    let action = match game {
+        Some(g) => Route::Game { game_id: g.game_id.clone() },
+        None => Route::Games,
+    };
+    layout(html! {
+        h1 { "Tarot" }
+        @if let Some(g) = game.filter(|g| g.is_finished()) {
+            (finished_banner(g))
+        }
+        (game_form(game, &action, registry))
+
+        @if let Some(g) = game {
+            p { a href=(url_for(&Route::GameAdmin { game_id: g.game_id.clone() })) { "Fusionner, séparer ou renuméroter" } }
+            p { a href=(url_for(&Route::GameHistory { game_id: g.game_id.clone() })) { "Historique des modifications" } }
+
+            button onclick="document.getElementById('delete-game-dialog').showModal();" {
+                img src=(DELETE_ICON) alt="" width="16" height="16";
+                " Supprimer le jeu"
+            }
+            dialog id="delete-game-dialog" {
+                p { "Are you sure you want to delete this game and all its hands? This can't be undone." }
+                form action=(url_for(&Route::Game { game_id: g.game_id.clone() })) method="POST" {
+                    input type="hidden" name="_method" value="DELETE";
+                    button type="submit" { "Yes, delete" }
+                    button type="submit" formmethod="dialog" { "No, cancel" }
+                }
+            }
+        }
+    })
+}
+
+/// The form to create a game pre-filled from `template`, as the next evening with the same group.
+pub fn html_new_game_like(template: &Game, registry: &[Player]) -> Markup {
     layout(html! {
         h1 { "Tarot" }
-        form action="/games" method="POST" {
+        p { "Nouveau jeu comme celui du " (template.date) ", chez " (template.host) "." }
+        (game_form(Some(template), &Route::Games, registry))
+    })
+}
+
+fn table_hand_numbers(hands: &[CompletedHand], table: &str) -> Vec<i32> {
+    let mut numbers: Vec<i32> = hands.iter().filter(|h| h.table == table).map(|h| h.hand_number).collect();
+    numbers.sort();
+    numbers
+}
+
+/// Tools to rearrange a game's hands: merging it into another game of the same evening,
+/// splitting one of its tables off into a game of its own, or renumbering a table's hands.
+pub fn html_game_admin(game: &Game, hands: &[CompletedHand], same_day_games: &[Game]) -> Markup {
+    let game_url = url_for(&Route::Game { game_id: game.game_id.clone() });
+    layout(html! {
+        h1 { a href=(game_url) { (game.date) ", chez " (game.host) } }
+
+        section {
+            h2 { "Fusionner avec un autre jeu" }
+            @if same_day_games.is_empty() {
+                p { "Pas d'autre jeu le " (game.date) "." }
+            } @else {
+                p { "Toutes les parties de ce jeu (" (hands.len()) ") seront déplacées dans l'autre jeu, puis ce jeu sera archivé." }
+                form action=(url_for(&Route::GameMerge { game_id: game.game_id.clone() })) method="POST"
+                    onsubmit="return confirm('Fusionner ces jeux ?');" {
+                    label for="targetGameId" { "Jeu" }
+                    select name="targetGameId" id="targetGameId" required {
+                        @for other in same_day_games {
+                            option value=(other.game_id) { "Chez " (other.host) " (" (other.players.join(", ")) ")" }
+                        }
+                    }
+                    button type="submit" { "Fusionner" }
+                }
+            }
+        }
+
+        section {
+            h2 { "Séparer une table" }
+            @if game.tables.len() < 2 {
+                p { "Ce jeu n'a qu'une table." }
+            } @else {
+                p { "La table et ses parties deviendront un jeu à part." }
+                form action=(url_for(&Route::GameSplit { game_id: game.game_id.clone() })) method="POST"
+                    onsubmit="return confirm('Séparer cette table ?');" {
+                    label for="table" { "Table" }
+                    select name="table" id="table" required {
+                        @for table in &game.tables {
+                            option value=(table) { (table) " (" (hands.iter().filter(|h| h.table == *table).count()) " parties)" }
+                        }
+                    }
+                    button type="submit" { "Séparer" }
+                }
+            }
+        }
+
+        section {
+            h2 { "Renuméroter une table" }
+            p { "Les parties de la table sont renumérotées de 1 à N dans l'ordre où elles ont été saisies, sans trous. Si certaines ont été saisies avant que l'heure de saisie soit enregistrée, l'ordre actuel des numéros est gardé." }
+            ul {
+                @for table in &game.tables {
+                    @let numbers = table_hand_numbers(hands, table);
+                    li {
+                        form .renumber action=(url_for(&Route::GameRenumber { game_id: game.game_id.clone() })) method="POST" {
+                            input type="hidden" name="table" value=(table);
+                            (table) " : "
+                            @if numbers.is_empty() { "pas de parties" } @else { "#" (numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", #")) }
+                            " "
+                            button type="submit" disabled[renumber_table(game, hands, table).is_empty()] { "Renuméroter" }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// The form to create a game, or edit one when `action` is the game's own route, filled in from
+/// `game` if there is one.
+fn game_form(game: Option<&Game>, action: &Route, registry: &[Player]) -> Markup {
+    let editing = matches!(action, Route::Game { .. });
+    html! {
+        form action=(url_for(action)) method="POST" {
+            @if let Some(g) = game.filter(|_| editing) {
+                input type="hidden" name="version" value=(g.version);
+            }
+            (submission_token())
             label for="date" { "Date" }
             input type="date" name="date" id="date" required value=(game.map(|g| g.date.clone()).unwrap_or("".to_string()));
 
             label for="host" { "Chez" }
             input type="text" name="host" id="date" required value=(game.map(|g| g.host.clone()).unwrap_or("".to_string()));
 
-            label for="players" { "Players" }
-            textarea name="players" rows="10" {
-                @if let Some(g) = game {
-                    (g.players.join("\n"))
+            label for="playerIds" { "Players" }
+            select name="playerIds" id="playerIds" multiple size="10" {
+                @for player in registry {
+                    option value=(player.player_id) selected[game.map(|g| g.player_ids.contains(&player.player_id)).unwrap_or(false)] {
+                        (player.display_name)
+                    }
                 }
-             }
+            }
+
+            @if let Some(g) = game.filter(|_| editing) {
+                fieldset .rename-players {
+                    legend { "Renommer les joueurs" }
+                    @for (player_id, name) in g.player_ids.iter().zip(g.players.iter()) {
+                        input type="text" name=(format!("playerName.{}", player_id)) aria-label=(name) value=(name);
+                    }
+                }
+            }
+
+            label for="newPlayers" { "Nouveaux joueurs" }
+            textarea name="newPlayers" id="newPlayers" rows="5" placeholder="Un nom par ligne" {}
+            p { a href=(url_for(&Route::Players)) { "Gérer les joueurs" } }
 
             label for="tables" { "Tables" }
             textarea name="tables" row="5" {
@@ -85,10 +223,104 @@ pub fn html_new_or_edit_game(game: Option<&Game>) -> Markup {
                 }
              }
 
+            label for="rankingMethod" { "Classement" }
+            select name="rankingMethod" id="rankingMethod" {
+                (select_options(
+                    vec![RankingMethod::Total, RankingMethod::AveragePerHand, RankingMethod::WeightedByTableSize],
+                    Some(game.map(|g| &g.ranking_method).unwrap_or(&RankingMethod::Total)),
+                    |v| v.to_string(),
+                    |v| v.to_string()
+                ))
+            }
+
+            label for="confirmation" { "Confirmation des parties par un autre joueur" }
+            select name="confirmation" id="confirmation" {
+                (select_options(
+                    vec![Confirmation::Off, Confirmation::Counted, Confirmation::Excluded],
+                    Some(game.map(|g| &g.confirmation).unwrap_or(&Confirmation::Off)),
+                    |v| v.to_string(),
+                    |v| v.to_string()
+                ))
+            }
+
+            @if let Some(g) = game.filter(|_| editing) {
+                label for="archived" {
+                    input type="checkbox" name="archived" id="archived" checked[g.archived];
+                    "Archivé (n'apparaît plus dans la liste des jeux)"
+                }
+            }
+
+            (author_field())
+
             button type="submit" { 
-                @if let Some(_) = game { "Modifier le jeu" } @else { "Créer le jeu" }
+                @if editing { "Modifier le jeu" } @else { "Créer le jeu" }
+            }
+        }
+    }
+}
+
+fn next_page_url(search: &GameSearch, cursor: &str) -> String {
+    let mut query = search.to_query();
+    query.push(("cursor", cursor));
+    format!("{}?{}", url_for(&Route::Index), serde_urlencoded::to_string(&query).unwrap())
+}
+
+/// The home page: the games (a page at a time, optionally filtered by `search`), and the form to
+/// create a new one.
+pub fn html_index(games: &[GameListing], search: &GameSearch, next_cursor: Option<&str>, registry: &[Player]) -> Markup {
+    layout(html! {
+        h1 { "Tarot" }
+
+        h2 { "Jeux" }
+        form .game-search action=(url_for(&Route::Index)) method="GET" {
+            label for="search-host" { "Chez" }
+            input type="text" name="host" id="search-host" value=(search.host);
+            label for="search-player" { "Joueur" }
+            input type="text" name="player" id="search-player" value=(search.player);
+            label for="search-from" { "Du" }
+            input type="date" name="from" id="search-from" value=(search.from);
+            label for="search-to" { "Au" }
+            input type="date" name="to" id="search-to" value=(search.to);
+            button type="submit" { "Rechercher" }
+            @if !search.is_empty() {
+                a href=(url_for(&Route::Index)) { "Tous les jeux" }
+            }
+        }
+
+        @if games.is_empty() {
+            p { @if search.is_empty() { "Pas encore de jeux." } @else { "Aucun jeu ne correspond à la recherche." } }
+        } @else {
+            table .games {
+                thead {
+                    tr {
+                        th { "Date" }
+                        th { "Chez" }
+                        th { "Joueurs" }
+                        th { "Parties" }
+                        th { "En tête" }
+                        th { "État" }
+                    }
+                }
+                tbody {
+                    @for listing in games {
+                        tr {
+                            td { a href=(url_for(&Route::Game { game_id: listing.game.game_id.clone() })) { (listing.game.date) } }
+                            td { (listing.game.host) }
+                            td .text-center { (listing.game.players.len()) }
+                            td .text-center { (listing.hand_count) }
+                            td { (listing.leader.as_deref().unwrap_or("—")) }
+                            td { (listing.game.status) }
+                        }
+                    }
+                }
             }
         }
+        @if let Some(cursor) = next_cursor {
+            p { a href=(next_page_url(search, cursor)) { "Jeux plus anciens" } }
+        }
+
+        h2 { "Nouveau jeu" }
+        (game_form(None, &Route::Games, registry))
     })
 }
 
@@ -109,17 +341,25 @@ where
 }
 
 
-pub fn hand_form(game: &Game, hand: Option<&CompletedHand>, next_hand_choices: Vec<(String, i32)>) -> Markup {
+pub fn hand_form(game: &Game, hand: Option<&CompletedHand>, next_hand_choices: Vec<(String, i32)>, return_to: Option<&Route>) -> Markup {
     let form_url = match hand {
-        Some(hand) => format!("/games/{}/hands/{}", game.game_id, hand.hand_id()),
-        None => format!("/games/{}/hands", game.game_id)
+        Some(hand) => url_for(&Route::GameHand { game_id: game.game_id.clone(), hand_id: hand.hand_id() }),
+        None => url_for(&Route::GameHands { game_id: game.game_id.clone() })
     };
     
     html! {
         form .hand-form id="hand-form" action=(form_url) method="POST" {
+            @if let Some(route) = return_to {
+                input type="hidden" name="returnTo" value=(url_for(route));
+            }
+            @if let Some(h) = hand {
+                input type="hidden" name="version" value=(h.version);
+            }
+            (submission_token())
+
             label for="bid" { "Partie" }
             select name="handId" id="handId" required {
-                @if let Some(ref h) = hand {
+                @if let Some(h) = hand {
                     option value=(h.hand_id()) selected { "Table \"" (h.table) "\" - Partie #" (h.hand_number) }
                 }
                 @for (table, hand_number) in next_hand_choices {
@@ -149,7 +389,7 @@ pub fn hand_form(game: &Game, hand: Option<&CompletedHand>, next_hand_choices: V
 
             label for="won" { "Gagné?" }
             select name="won" id="won" {
-                @if let Some(ref h) = hand {
+                @if let Some(h) = hand {
                     option id="won-true" value="true" selected[h.won] { "Oui, " (h.bidder) " a gagné le contrat" }
                     option id="won-false" value="false" selected[!h.won] { "Non, " (h.bidder) " a perdu le contrat" }
                 } @else {
@@ -192,12 +432,24 @@ pub fn hand_form(game: &Game, hand: Option<&CompletedHand>, next_hand_choices: V
                 ))
             }
 
-            button type="submit" { @if let Some(_) = hand { "Modifier" } @else { "Ajouter" } }
+            label for="notes" { "Notes" }
+            textarea name="notes" id="notes" rows="2" placeholder="Par exemple : poignée à revérifier" {
+                (hand.map(|h| h.notes.clone()).unwrap_or_default())
+            }
+
+            label for="disputed" {
+                input type="checkbox" name="disputed" id="disputed" checked[hand.map(|h| h.disputed).unwrap_or(false)];
+                "Partie contestée"
+            }
+
+            (author_field())
+
+            button type="submit" { @if hand.is_some() { "Modifier" } @else { "Ajouter" } }
         }
     }
 }
 
-fn get_next_hand_choices(tables: &Vec<String>, hands: &Vec<&CompletedHand>) -> Vec<(String, i32)> {
+fn get_next_hand_choices(tables: &[String], hands: &[&CompletedHand]) -> Vec<(String, i32)> {
     tables.iter().map(|table| {
         let mut hand_number = 1;
         for hand in hands {
@@ -210,129 +462,534 @@ fn get_next_hand_choices(tables: &Vec<String>, hands: &Vec<&CompletedHand>) -> V
 }
 
 
-pub fn html_game(game: &Game, hands: &Vec<(CompletedHand, HashMap<String, i32>)>, total_scores: &HashMap<String, i32>, player_hand_count: &HashMap<String, i32>) -> Markup {
-    let qrcode_url = url_for(&Route::GameQRCode { game_id: game.game_id.clone() });
-
-    layout(html! {
-        h1 { (game.date) ", chez " (game.host) }
-
-        dialog id="qrcode-dialog" {
+fn qrcode_dialog(dialog_id: &str, qrcode_url: &str) -> Markup {
+    html! {
+        dialog id=(dialog_id) {
             form autofocus method="dialog" {
                 button .icon.close {
                     img src=(CLOSE_ICON) alt="Close" width="24" height="24";
                 }
             }
-            img .qrcode src=(qrcode_url) alt="QR Code";
+            img .qrcode src=(qrcode_url) alt="QR Code" loading="lazy";
+        }
+    }
+}
+
+/// Who is saving the form, for the game's history.  script.js remembers it on the device.
+fn author_field() -> Markup {
+    html! {
+        label for="author" { "Saisi par" }
+        input type="text" name="author" id="author" placeholder="Votre nom (facultatif)";
+    }
+}
+
+/// A fresh one-time token for a form, so that submitting it twice only saves it once.
+fn submission_token() -> Markup {
+    html! {
+        input type="hidden" name="submissionToken" value=(Uuid::new_v4().to_string());
+    }
+}
+
+fn nav_button(section: &str, icon: &str, label: &str) -> Markup {
+    html! {
+        button onclick="toggleNavigableSection(event)" data-navigable=(section) {
+            img src=(icon) alt=(label) width="16" height="16";
+            (label)
+        }
+    }
+}
+
+pub fn html_game(
+    game: &Game,
+    summary: &GameSummary,
+    stats: &[PlayerStats],
+    timing: &SessionTiming,
+    uncounted: &[CompletedHand],
+    trash: &[CompletedHand],
+    deleted: Option<&CompletedHand>,
+) -> Markup {
+    let hands = &summary.hands_with_scores;
+    let all_hands = with_uncounted(hands, uncounted);
+    let qrcode_url = url_for(&Route::GameQRCode { game_id: game.game_id.clone() });
+    // Score columns follow the leaderboard, then whoever hasn't played yet.
+    let mut players_by_position: Vec<String> = summary.leaderboard.iter().map(|e| e.player.clone()).collect();
+    players_by_position.extend(game.players.iter().filter(|p| !summary.total_scores.contains_key(*p)).cloned());
+    let duplicates = find_duplicates(&all_hands);
+
+    layout(html! {
+        h1 { (game.date) ", chez " (game.host) }
+        @if game.archived {
+            p .archived-banner { "Ce jeu est archivé." }
+        }
+        @if game.is_finished() {
+            (finished_banner(game))
+        }
+        @if let Some(hand) = deleted {
+            div .undo-banner {
+                "Partie #" (hand.hand_number) " de la table " (hand.table) " supprimée. "
+                (restore_form(game, hand, "Annuler"))
+            }
         }
+
+        (qrcode_dialog("qrcode-dialog", &qrcode_url))
         
         button class="icon qrcode" onclick="document.getElementById('qrcode-dialog').showModal();" { 
             img src=(QRCODE_ICON) alt="QR Code" width="24" height="24";
          }
+        a .icon role="button" href=(url_for(&Route::GameEdit { game_id: game.game_id.clone() })) {
+            img src=(EDIT_ICON) alt="Modifier le jeu" width="24" height="24";
+        }
+        a .icon role="button" href=(url_for(&Route::GameClone { game_id: game.game_id.clone() })) {
+            img src=(CONTENT_COPY_ICON) alt="Nouveau jeu comme celui-ci" width="24" height="24";
+        }
         
         nav {
-            button onclick="toggleNavigableSection(event)" data-navigable="summary" {
-                img src=(GROUPS_ICON) alt="Résumé" width="16" height="16";
-                "Résumé"
-            }
-            button onclick="toggleNavigableSection(event)" data-navigable="hands" {
-                img src=(PLAYING_CARDS_ICON) alt="Parties" width="16" height="16";
-                "Parties"
-            }
-            button onclick="toggleNavigableSection(event)" data-navigable="scores" {
-                img src=(SCOREBOARD_ICON) alt="Scores" width="16" height="16";
-                "Scores"
-            }
-            button onclick="toggleNavigableSection(event)" data-navigable="add-hand" {
-                img src=(ADD_ICON) alt="Ajoute une partie" width="16" height="16";
-                "Ajouter"
+            (nav_button("summary", GROUPS_ICON, "Résumé"))
+            (nav_button("hands", PLAYING_CARDS_ICON, "Parties"))
+            (nav_button("scores", SCOREBOARD_ICON, "Scores"))
+            (nav_button("chart", SHOW_CHART_ICON, "Évolution"))
+            (nav_button("stats", BAR_CHART_ICON, "Statistiques"))
+            @if !game.is_finished() {
+                (nav_button("add-hand", ADD_ICON, "Ajouter"))
             }
         }
         
         section data-navigable="summary" {
+            @if game.status == GameStatus::Planned {
+                p { "Ce jeu n'a pas encore commencé : la première partie saisie le lancera." }
+            }
+            @if game.is_finished() {
+                h2 { "Classement final" }
+                p { "Par " (game.ranking_method) }
+                (ranking_table(&game.ranking_method, &game.final_standings))
+            } @else if !summary.ranking.is_empty() {
+                h2 { "Classement" }
+                p { "Par " (game.ranking_method) }
+                (ranking_table(&game.ranking_method, &summary.ranking))
+            }
+
             h2 { "Joueurs" }
             @if !game.players.is_empty() {
-                table .text-center {
-                    thead {
-                        tr {
-                            th { "Joueur" }
-                            th { "# parties" }
-                        }
-                    }
-                    tbody {
-                        @for player in &game.players {
-                            tr { 
-                                td { (player) }
-                                td { (player_hand_count.get(player).map(|i| i.to_string()).unwrap_or("".to_string())) }
-                            }
-                        }
-                    }
-                }
+                (players_table(&game.players, &summary.player_hand_count))
             }
             
             @if !game.tables.is_empty() {
                 section {
                     h2 { "Tables" }
                     ul {
-                        @for table in &game.tables {
-                            li { (table) }
+                        @for (i, table) in game.tables.iter().enumerate() {
+                            @let dialog_id = format!("table-qrcode-dialog-{}", i);
+                            li {
+                                a href=(url_for(&Route::GameTable { game_id: game.game_id.clone(), table: table.clone() })) { (table) }
+                                " "
+                                button .icon onclick=(format!("document.getElementById('{}').showModal();", dialog_id)) {
+                                    img src=(QRCODE_ICON) alt="QR Code" width="16" height="16";
+                                }
+                                (qrcode_dialog(&dialog_id, &url_for(&Route::GameTableQRCode { game_id: game.game_id.clone(), table: table.clone() })))
+                            }
                         }
                     }
                 }
             }
+
+            @if timing.session_minutes.is_some() {
+                (timing_summary(timing))
+            }
+
+            p { a href=(url_for(&Route::GameHistory { game_id: game.game_id.clone() })) { "Historique des modifications" } }
+
+            @if game.status == GameStatus::InProgress {
+                (finish_form(game))
+            }
         }
 
         section data-navigable="hands" hidden {
             h2 { "Parties" }
+            @if !duplicates.is_empty() {
+                (duplicates_list(game, &duplicates))
+            }
+            @if !all_hands.is_empty() {
+                (hands_table(game, &all_hands, None))
+            }
+            @if !trash.is_empty() {
+                (trash_list(game, trash))
+            }
+        }
+
+        section data-navigable="scores" hidden {
+            h2 { "Scores" }
+            @if !hands.is_empty() {
+                h3 { "Classement au total" }
+                (leaderboard_table(&summary.leaderboard))
+
+                h3 { "Partie par partie" }
+                (scores_table(&players_by_position, summary))
+            }
+        }
+
+        section data-navigable="chart" hidden {
+            h2 { "Évolution" }
+            @if !hands.is_empty() {
+                (score_chart(&game.players, hands, &summary.running_totals))
+            }
+        }
+
+        section data-navigable="stats" hidden {
+            h2 { "Statistiques" }
             @if !hands.is_empty() {
-                (hands_table(&game, hands))
+                (stats_table(stats))
+            }
+        }
+
+        @if !game.is_finished() {
+            section data-navigable="add-hand" hidden {
+                h2 { "Ajoute une partie" }
+                @let next_hand_choices = get_next_hand_choices(&game.tables, &all_hands);
+                (hand_form(game, None, next_hand_choices, None))
+            }
+        }
+    })
+}
+
+pub fn html_game_table(
+    game: &Game,
+    table: &str,
+    summary: &GameSummary,
+    uncounted: &[CompletedHand],
+) -> Markup {
+    let hands = &summary.hands_with_scores;
+    let all_hands = with_uncounted(hands, uncounted);
+    let table_route = Route::GameTable { game_id: game.game_id.clone(), table: table.to_string() };
+    let qrcode_url = url_for(&Route::GameTableQRCode { game_id: game.game_id.clone(), table: table.to_string() });
+    // The table's roster is whoever has played a hand there, in the game's player order.
+    let roster: Vec<String> = game.players.iter()
+        .filter(|p| summary.player_hand_count.contains_key(*p))
+        .cloned()
+        .collect();
+    let next_hand_choices = get_next_hand_choices(&[table.to_string()], &all_hands);
+    let next_hand_number = next_hand_choices.first().map(|(_, n)| *n).unwrap_or(1);
+
+    layout(html! {
+        h1 { "Table " (table) }
+        p {
+            a href=(url_for(&Route::Game { game_id: game.game_id.clone() })) { (game.date) ", chez " (game.host) }
+        }
+
+        (qrcode_dialog("qrcode-dialog", &qrcode_url))
+
+        button class="icon qrcode" onclick="document.getElementById('qrcode-dialog').showModal();" { 
+            img src=(QRCODE_ICON) alt="QR Code" width="24" height="24";
+         }
+
+        nav {
+            (nav_button("summary", GROUPS_ICON, "Résumé"))
+            (nav_button("hands", PLAYING_CARDS_ICON, "Parties"))
+            (nav_button("scores", SCOREBOARD_ICON, "Scores"))
+            @if !game.is_finished() {
+                (nav_button("add-hand", ADD_ICON, "Ajouter"))
+            }
+        }
+
+        section data-navigable="summary" {
+            @if game.is_finished() {
+                (finished_banner(game))
+            } @else {
+                p { "Prochaine partie : #" (next_hand_number) }
+            }
+            h2 { "Joueurs" }
+            @if roster.is_empty() {
+                p { "Aucune partie n'a encore été jouée à cette table." }
+            } @else {
+                (players_table(&roster, &summary.player_hand_count))
+            }
+        }
+
+        section data-navigable="hands" hidden {
+            h2 { "Parties" }
+            @if !all_hands.is_empty() {
+                (hands_table(game, &all_hands, Some(&table_route)))
             }
         }
 
         section data-navigable="scores" hidden {
             h2 { "Scores" }
             @if !hands.is_empty() {
-                table .text-center {
-                    thead {
-                        tr {
-                            th { "Table" }
-                            th { "Partie #" }
-                            @for player in &game.players {
-                                th { (player) }
+                (scores_table(&roster, summary))
+            }
+        }
+
+        @if !game.is_finished() {
+            section data-navigable="add-hand" hidden {
+                h2 { "Ajoute une partie" }
+                (hand_form(game, None, next_hand_choices, Some(&table_route)))
+            }
+        }
+    })
+}
+
+/// Finishing freezes the standings, so every hand must be settled first.
+fn finish_form(game: &Game) -> Markup {
+    html! {
+        form .finish action=(url_for(&Route::GameFinish { game_id: game.game_id.clone() })) method="POST"
+            onsubmit="return confirm('Terminer ce jeu ? Le classement sera figé et plus rien ne pourra être modifié.');" {
+            (submission_token())
+            // Filled in by script.js with the name last given on this device.
+            input type="hidden" name="author";
+            button type="submit" { "Terminer le jeu" }
+        }
+    }
+}
+
+/// Says a game is finished, with the form to reopen it.
+fn finished_banner(game: &Game) -> Markup {
+    html! {
+        div .finished-banner {
+            "Ce jeu est terminé : son classement est figé et ses parties ne peuvent plus être modifiées. "
+            form action=(url_for(&Route::GameReopen { game_id: game.game_id.clone() })) method="POST"
+                onsubmit="return confirm('Rouvrir ce jeu ? Son classement pourra de nouveau changer.');" {
+                (submission_token())
+                input type="hidden" name="author";
+                button type="submit" { "Rouvrir le jeu" }
+            }
+        }
+    }
+}
+
+fn ranking_table(method: &RankingMethod, ranking: &[(String, f64)]) -> Markup {
+    html! {
+        table .text-center {
+            thead {
+                tr {
+                    th { "#" }
+                    th { "Joueur" }
+                    th { "Score" }
+                }
+            }
+            tbody {
+                @for (i, (player, score)) in ranking.iter().enumerate() {
+                    tr {
+                        td { ((i + 1)) }
+                        td { (player) }
+                        td {
+                            @if *method == RankingMethod::Total {
+                                (score)
+                            } @else {
+                                (format!("{:.1}", score))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn players_table(players: &[String], player_hand_count: &HashMap<String, i32>) -> Markup {
+    html! {
+        table .text-center {
+            thead {
+                tr {
+                    th { "Joueur" }
+                    th { "# parties" }
+                }
+            }
+            tbody {
+                @for player in players {
+                    tr { 
+                        td { (player) }
+                        td { (player_hand_count.get(player).map(|i| i.to_string()).unwrap_or("".to_string())) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn leaderboard_table(leaderboard: &[LeaderboardEntry]) -> Markup {
+    html! {
+        table .text-center {
+            thead {
+                tr {
+                    th { "#" }
+                    th { "Joueur" }
+                    th { "Total" }
+                    th { "Écart" }
+                    th { "Évolution" }
+                }
+            }
+            tbody {
+                @for entry in leaderboard {
+                    tr .moved-up[entry.movement > 0] .moved-down[entry.movement < 0] {
+                        td { (entry.position) @if entry.tied { "=" } }
+                        td { (entry.player) }
+                        td { (entry.total) }
+                        td { @if entry.gap_to_leader > 0 { "-" (entry.gap_to_leader) } }
+                        td {
+                            @if entry.movement > 0 {
+                                "▲ " (entry.movement)
+                            } @else if entry.movement < 0 {
+                                "▼ " (-entry.movement)
                             }
                         }
                     }
-                    tbody {
-                        @for (hand, scores) in hands {
-                            tr {
-                                td { (hand.table) }
-                                td { (hand.hand_number) }
-                                @for player in &game.players {
-                                    th { (scores.get(player).map(|i| i.to_string()).unwrap_or("".to_string())) }
+                }
+            }
+        }
+    }
+}
+
+fn percentage(rate: Option<f64>) -> String {
+    rate.map(|r| format!("{:.0}%", r * 100.0)).unwrap_or("-".to_string())
+}
+
+fn stats_table(stats: &[PlayerStats]) -> Markup {
+    html! {
+        table .stats.text-center {
+            thead {
+                tr {
+                    th { "Joueur" }
+                    th { "Prises" }
+                    th { "Réussite" }
+                    th { "Par contrat" }
+                    th { "Marge moyenne" }
+                    th { "Preneur / Appelé / Défense" }
+                    th { "Petits au bout" }
+                    th { "Poignées" }
+                    th { "Chelems" }
+                    th { "Meilleure partie" }
+                    th { "Pire partie" }
+                }
+            }
+            tbody {
+                @for player in stats {
+                    tr {
+                        td { (player.player) }
+                        td { (player.contracts_taken()) }
+                        td {
+                            (percentage(if player.contracts_taken() > 0 {
+                                Some(player.contracts_won() as f64 / player.contracts_taken() as f64)
+                            } else {
+                                None
+                            }))
+                        }
+                        td {
+                            div .cols {
+                                @for contract in player.contracts.iter().filter(|c| c.taken > 0) {
+                                    span { (contract.bid) " : " (contract.won) "/" (contract.taken) " (" (percentage(contract.success_rate())) ")" }
                                 }
                             }
                         }
+                        td { (player.average_margin.map(|m| format!("{:+.1}", m)).unwrap_or("-".to_string())) }
+                        td { (player.as_bidder) " / " (player.as_partner) " / " (player.as_defender) }
+                        td { (player.petits_au_bout) }
+                        td { (player.poignees) }
+                        td { (player.chelems) }
+                        td { (player.biggest_win.map(|s| format!("{:+}", s)).unwrap_or("-".to_string())) }
+                        td { (player.biggest_loss.map(|s| s.to_string()).unwrap_or("-".to_string())) }
+                    }
+                }
+            }
+        }
+    }
+}
 
-                        tr {
-                            td colspan="2" { b { "Total" } }
-                            @for player in &game.players {
-                                th { (total_scores.get(player).map(|i| i.to_string()).unwrap_or("".to_string())) }
+fn scores_table(players: &[String], summary: &GameSummary) -> Markup {
+    html! {
+        table .text-center {
+            thead {
+                tr {
+                    th { "Table" }
+                    th { "Partie #" }
+                    @for player in players {
+                        th { (player) }
+                    }
+                }
+            }
+            tbody {
+                @for ((hand, scores), totals) in summary.hands_with_scores.iter().zip(&summary.running_totals) {
+                    tr.disputed[hand.disputed] title=[hand.disputed.then_some("Partie contestée")] {
+                        td { (hand.table) }
+                        td { (hand.hand_number) }
+                        @for player in players {
+                            th {
+                                (scores.get(player).map(|i| i.to_string()).unwrap_or("".to_string()))
+                                @if let Some(total) = totals.get(player) {
+                                    br;
+                                    small .running-total { (total) }
+                                }
                             }
                         }
                     }
                 }
+
+                tr {
+                    td colspan="2" { b { "Total" } }
+                    @for player in players {
+                        th { (summary.total_scores.get(player).map(|i| i.to_string()).unwrap_or("".to_string())) }
+                    }
+                }
             }
         }
+    }
+}
 
-        section data-navigable="add-hand" hidden {
-            h2 { "Ajoute une partie" }
-            @let next_hand_choices = get_next_hand_choices(&game.tables, &hands.iter().map(|(h, _)| h).collect());
-            (hand_form(&game, None, next_hand_choices))
+/// How long the session has lasted, and each table's pace.
+fn timing_summary(timing: &SessionTiming) -> Markup {
+    html! {
+        section {
+            h2 { "Rythme" }
+            @if let Some(minutes) = timing.session_minutes {
+                p { "Durée de la session : " (format!("{} h {:02}", minutes / 60, minutes % 60)) }
+            }
+            table .text-center {
+                thead {
+                    tr { th { "Table" } th { "Minutes par partie" } }
+                }
+                tbody {
+                    @for pace in &timing.tables {
+                        tr {
+                            td { (pace.table) }
+                            td { (pace.minutes_per_hand.map(|m| format!("{:.1}", m)).unwrap_or("-".to_string())) }
+                        }
+                    }
+                }
+            }
         }
-    })
+    }
+}
+
+/// The scored hands along with those left out of the scores until they're confirmed, in hand ID
+/// order.
+fn with_uncounted<'a>(hands: &'a [(CompletedHand, HashMap<String, i32>)], uncounted: &'a [CompletedHand]) -> Vec<&'a CompletedHand> {
+    let mut all_hands: Vec<&CompletedHand> = hands.iter().map(|(h, _)| h).chain(uncounted.iter()).collect();
+    all_hands.sort_by_key(|h| h.hand_id());
+    all_hands
 }
 
-fn hands_table(game: &Game, hands: &Vec<(CompletedHand, HashMap<String, i32>)>) -> Markup {
+/// Each hand's position when they're put in the order they were entered.  Hands from before entry
+/// times were recorded come first.
+fn chronological_positions(hands: &[&CompletedHand]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..hands.len()).collect();
+    order.sort_by_key(|i| (hands[*i].created_at.clone(), *i));
+    let mut positions = vec![0; hands.len()];
+    for (position, i) in order.into_iter().enumerate() {
+        positions[i] = position;
+    }
+    positions
+}
+
+/// The hands, with the means to edit and delete them and to confirm those that are pending, coming
+/// back to `return_to` (or else the game page) afterwards.  A finished game's hands are read-only.
+fn hands_table(game: &Game, hands: &[&CompletedHand], return_to: Option<&Route>) -> Markup {
+    let chronological = chronological_positions(hands);
     html! {
+        label .hands-order {
+            "Ordre "
+            select onchange="sortHandsTable(this)" {
+                option value="number" { "Par numéro" }
+                option value="chronological" { "Chronologique" }
+            }
+        }
         table .hands.text-center {
             thead {
                 tr {
@@ -348,30 +1005,43 @@ fn hands_table(game: &Game, hands: &Vec<(CompletedHand, HashMap<String, i32>)>)
                 }
             }
             tbody {
-                @for (hand, _) in hands {
+                @for (i, hand) in hands.iter().enumerate() {
                     @let route_url = url_for(&Route::GameHand { game_id: game.game_id.clone(), hand_id: hand.hand_id() });
-                    tr {
+                    tr.pending[game.is_pending(hand)].disputed[hand.disputed] data-number=(i) data-chronological=(chronological[i]) {
                         td {
                             div.cols {
                                 span { (hand.table) ", #" (hand.hand_number) }
-                                span {
-                                    a .icon role="button" href=(route_url) { 
-                                        img src=(EDIT_ICON) alt="Edit" width="16" height="16";
-                                    }
-                                    button .icon onclick=(format!("document.getElementById('delete-dialog-{}').showModal();", hand.hand_id())) { 
-                                        img src=(DELETE_ICON) alt="Delete" width="16" height="16";
-                                    }
-                                    
-                                    dialog id=(format!("delete-dialog-{}", hand.hand_id())) {
-                                        p { "Are you sure you want to delete this hand?" }
-                                        form action=(route_url) method="POST" {
-                                            input type="hidden" name="_method" value="DELETE";
-                                            button type="submit" { "Yes, delete" }
-                                            button type="submit" formmethod="dialog" { "No, cancel" }
+                                @if !game.is_finished() {
+                                    span {
+                                        a .icon role="button" href=(route_url) { 
+                                            img src=(EDIT_ICON) alt="Edit" width="16" height="16";
+                                        }
+                                        button .icon onclick=(format!("document.getElementById('delete-dialog-{}').showModal();", hand.hand_id())) { 
+                                            img src=(DELETE_ICON) alt="Delete" width="16" height="16";
+                                        }
+                                        
+                                        dialog id=(format!("delete-dialog-{}", hand.hand_id())) {
+                                            p { "Are you sure you want to delete this hand?" }
+                                            form action=(route_url) method="POST" {
+                                                input type="hidden" name="_method" value="DELETE";
+                                                button type="submit" { "Yes, delete" }
+                                                button type="submit" formmethod="dialog" { "No, cancel" }
+                                            }
                                         }
                                     }
                                 }
                             }
+                            @if hand.disputed {
+                                span .badge.disputed { "Contestée" }
+                            }
+                            @if !hand.notes.is_empty() {
+                                p .notes { (hand.notes) }
+                            }
+                            @if game.is_pending(hand) && !game.is_finished() {
+                                (confirm_hand(game, hand, return_to))
+                            } @else if let Some(confirmed_by) = hand.confirmed_by.as_ref().filter(|_| game.confirmation != Confirmation::Off) {
+                                small { "confirmée par " (confirmed_by) }
+                            }
                         }
                         td { (hand.bid) }
                         td { 
@@ -404,10 +1074,77 @@ fn hands_table(game: &Game, hands: &Vec<(CompletedHand, HashMap<String, i32>)>)
     }
 }
 
-pub fn html_edit_hand(game: &Game, hands: &Vec<CompletedHand>, hand: &CompletedHand) -> Markup {
+/// A pending hand's status, with a button for another of its players to confirm it.
+fn confirm_hand(game: &Game, hand: &CompletedHand, return_to: Option<&Route>) -> Markup {
+    let dialog_id = format!("confirm-dialog-{}", hand.hand_id());
+    let confirmers: Vec<&String> = hand.players.iter()
+        .filter(|p| !hand.entered_by.as_deref().is_some_and(|e| same_name(e, p)))
+        .collect();
+    html! {
+        div .pending-status {
+            span .badge { "À confirmer" }
+            @if let Some(entered_by) = &hand.entered_by {
+                small { "saisie par " (entered_by) }
+            }
+            button onclick=(format!("document.getElementById('{}').showModal();", dialog_id)) { "Confirmer" }
+            dialog id=(dialog_id) {
+                p { "Qui confirme la partie #" (hand.hand_number) " de la table " (hand.table) " ?" }
+                form action=(url_for(&Route::GameHandConfirm { game_id: game.game_id.clone(), hand_id: hand.hand_id() })) method="POST" {
+                    @if let Some(route) = return_to {
+                        input type="hidden" name="returnTo" value=(url_for(route));
+                    }
+                    select name="confirmedBy" required {
+                        option value="" { "" }
+                        @for player in confirmers {
+                            option value=(player) { (player) }
+                        }
+                    }
+                    button type="submit" { "Confirmer" }
+                    button type="submit" formmethod="dialog" formnovalidate { "Annuler" }
+                }
+            }
+        }
+    }
+}
+
+pub fn html_edit_hand(game: &Game, hands: &[CompletedHand], hand: &CompletedHand) -> Markup {
     layout(html! {
         h1 { "Edit Hand" }
-        (hand_form(game, Some(hand), get_next_hand_choices(&game.tables, &hands.iter().collect())))
+        (hand_form(game, Some(hand), get_next_hand_choices(&game.tables, &hands.iter().collect::<Vec<_>>()), None))
+    })
+}
+
+pub fn html_players(players: &[Player]) -> Markup {
+    layout(html! {
+        h1 { "Joueurs" }
+        p { a href=(url_for(&Route::Index)) { "Retour" } }
+        @if players.is_empty() {
+            p { "Aucun joueur pour l'instant : ils sont ajoutés en créant un jeu." }
+        } @else {
+            table .text-center {
+                thead {
+                    tr {
+                        th { "Nom" }
+                        th { "Alias (un par ligne)" }
+                        th {}
+                    }
+                }
+                tbody {
+                    @for player in players {
+                        @let form_id = format!("player-{}", player.player_id);
+                        tr {
+                            td { input type="text" name="displayName" form=(form_id) required value=(player.display_name); }
+                            td { textarea name="aliases" form=(form_id) rows="2" { (player.aliases.join("\n")) } }
+                            td {
+                                form id=(form_id) action=(url_for(&Route::Player { player_id: player.player_id.clone() })) method="POST" {
+                                    button type="submit" { "Modifier" }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
     })
 }
 
@@ -428,6 +1165,285 @@ pub fn html_game_not_found(game_id: &str) -> Markup {
     })
 }
 
+/// A hand's details, for showing it outside the hands table.
+fn hand_details(hand: &CompletedHand) -> Markup {
+    html! {
+        dl .hand-details {
+            dt { "Partie" } dd { "Table " (hand.table) ", #" (hand.hand_number) }
+            dt { "Contrat" } dd { (hand.bid) }
+            dt { "Preneur" } dd {
+                (hand.bidder) " "
+                (match &hand.partner {
+                    Some(p) => format!("avec {}", p),
+                    None => "seul(e)".to_string()
+                })
+            }
+            dt { "Défense" } dd { (hand.defence.join(", ")) }
+            dt { "Résultat" } dd {
+                (if hand.won { "gagnée" } else { "chutée" }) " de " (hand.won_or_lost_by)
+                @if hand.petit_au_bout { ", avec petit au bout" }
+                @if hand.poignee != Poignée::Aucune { ", avec une poignée " (hand.poignee) }
+                @if hand.chelem != Chelem::Aucun { ", avec un chelem " (hand.chelem) }
+            }
+            @if hand.disputed {
+                dt { "Contestée" } dd { "oui" }
+            }
+            @if !hand.notes.is_empty() {
+                dt { "Notes" } dd { (hand.notes) }
+            }
+        }
+    }
+}
+
+/// The fields of the hand form for `hand`, hidden, so that it can be submitted again as is.
+fn hand_hidden_fields(hand: &CompletedHand, version: i32) -> Markup {
+    html! {
+        input type="hidden" name="handId" value=(hand.hand_id());
+        input type="hidden" name="bid" value=(hand.bid);
+        input type="hidden" name="bidder" value=(hand.bidder);
+        input type="hidden" name="partner" value=(hand.partner.clone().unwrap_or_default());
+        @for player in &hand.defence {
+            input type="hidden" name="defence" value=(player);
+        }
+        input type="hidden" name="won" value=(hand.won);
+        input type="hidden" name="wonOrLostBy" value=(hand.won_or_lost_by);
+        @if hand.petit_au_bout {
+            input type="hidden" name="petitAuBout" value="on";
+        }
+        input type="hidden" name="poignee" value=(hand.poignee);
+        input type="hidden" name="chelem" value=(hand.chelem);
+        input type="hidden" name="notes" value=(hand.notes);
+        @if hand.disputed {
+            input type="hidden" name="disputed" value="on";
+        }
+        input type="hidden" name="version" value=(version);
+    }
+}
+
+/// The page for when saving `mine` (moved from `old_hand_id`, if it was being edited) found that
+/// someone else had just saved `theirs` in its place, or deleted it.
+pub fn html_hand_conflict(game: &Game, mine: &CompletedHand, theirs: Option<&CompletedHand>, old_hand_id: Option<&str>) -> Markup {
+    let game_url = url_for(&Route::Game { game_id: game.game_id.clone() });
+    // Replacing theirs saves mine again from where it was, so that a hand being moved onto theirs
+    // doesn't also stay where it was.
+    let old_hand_id = old_hand_id.map(|id| id.to_string()).unwrap_or_else(|| mine.hand_id());
+    let replaces_destination = theirs.is_some_and(|theirs| theirs.hand_id() == mine.hand_id() && theirs.hand_id() != old_hand_id);
+    layout(html! {
+        h1 { "Partie déjà enregistrée" }
+        @match theirs {
+            Some(theirs) => {
+                p { "Quelqu'un d'autre vient d'enregistrer la partie #" (mine.hand_number) " de la table " (mine.table) "." }
+                div .conflict {
+                    section {
+                        h2 { "Déjà enregistrée" }
+                        (hand_details(theirs))
+                        a role="button" href=(game_url) { "Garder celle-ci" }
+                    }
+                    section {
+                        h2 { "Votre saisie" }
+                        (hand_details(mine))
+                        form action=(url_for(&Route::GameHand { game_id: game.game_id.clone(), hand_id: old_hand_id.clone() })) method="POST" {
+                            @if replaces_destination {
+                                (hand_hidden_fields(mine, mine.version))
+                                input type="hidden" name="replaceVersion" value=(theirs.version);
+                            } @else {
+                                (hand_hidden_fields(mine, theirs.version))
+                            }
+                            button type="submit" { "La remplacer par la vôtre" }
+                        }
+                    }
+                }
+            },
+            None => {
+                p { "Quelqu'un d'autre vient de supprimer la partie #" (mine.hand_number) " de la table " (mine.table) "." }
+                section {
+                    h2 { "Votre saisie" }
+                    (hand_details(mine))
+                    form action=(url_for(&Route::GameHands { game_id: game.game_id.clone() })) method="POST" {
+                        (hand_hidden_fields(mine, 0))
+                        button type="submit" { "L'enregistrer quand même" }
+                    }
+                    a href=(game_url) { "Revenir au jeu" }
+                }
+            },
+        }
+    })
+}
+
+/// The page asking whether `mine` really is a new hand, since it looks like `duplicate` entered
+/// again.  Confirming submits it to `action` again, with the `passthrough` fields it came with.
+pub fn html_suspected_duplicate(game: &Game, mine: &CompletedHand, duplicate: &CompletedHand, action: &Route, passthrough: &[(String, String)]) -> Markup {
+    layout(html! {
+        h1 { "Partie en double ?" }
+        p { "Cette partie ressemble beaucoup à la partie #" (duplicate.hand_number) " de la table " (duplicate.table) ", déjà enregistrée." }
+        div .conflict {
+            section {
+                h2 { "Déjà enregistrée" }
+                (hand_details(duplicate))
+                a role="button" href=(url_for(&Route::Game { game_id: game.game_id.clone() })) { "C'est la même, annuler" }
+            }
+            section {
+                h2 { "Votre saisie" }
+                (hand_details(mine))
+                form action=(url_for(action)) method="POST" {
+                    (hand_hidden_fields(mine, mine.version))
+                    @for (name, value) in passthrough {
+                        input type="hidden" name=(name) value=(value);
+                    }
+                    input type="hidden" name="confirmDuplicate" value="true";
+                    button type="submit" { "C'est une autre partie, l'enregistrer" }
+                }
+            }
+        }
+    })
+}
+
+/// Hands that look like they were entered twice, each with a button to delete the second one unless
+/// the game is finished.
+fn duplicates_list(game: &Game, duplicates: &[(&CompletedHand, &CompletedHand)]) -> Markup {
+    html! {
+        div .duplicates {
+            h3 { "Doublons possibles" }
+            ul {
+                @for (first, second) in duplicates {
+                    li {
+                        "Table " (first.table) " : #" (first.hand_number) " et #" (second.hand_number) " sont identiques. "
+                        @if !game.is_finished() {
+                            form action=(url_for(&Route::GameHand { game_id: game.game_id.clone(), hand_id: second.hand_id() })) method="POST" {
+                                input type="hidden" name="_method" value="DELETE";
+                                button type="submit" { "Supprimer la #" (second.hand_number) }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn restore_form(game: &Game, hand: &CompletedHand, label: &str) -> Markup {
+    html! {
+        form action=(url_for(&Route::GameHandRestore { game_id: game.game_id.clone(), hand_id: hand.trash_id().unwrap_or_default() })) method="POST" {
+            button type="submit" { (label) }
+        }
+    }
+}
+
+/// Deleted hands, which can be restored until they're purged.
+fn trash_list(game: &Game, trash: &[CompletedHand]) -> Markup {
+    html! {
+        div .trash {
+            h3 { "Corbeille" }
+            p { "Les parties supprimées sont effacées pour de bon au bout de 30 jours." }
+            ul {
+                @for hand in trash {
+                    li {
+                        "Table " (hand.table) ", #" (hand.hand_number) " : " (hand.bid) " de " (hand.bidder)
+                        ", " (if hand.won { "gagnée" } else { "chutée" }) " de " (hand.won_or_lost_by)
+                        @if let Some(deleted_at) = &hand.deleted_at {
+                            " (supprimée le " (format_timestamp(deleted_at)) ") "
+                        }
+                        @if !game.is_finished() {
+                            (restore_form(game, hand, "Restaurer"))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Every change made to a game and its hands, most recent first.
+pub fn html_game_history(game: &Game, entries: &[HistoryEntry]) -> Markup {
+    layout(html! {
+        h1 { a href=(url_for(&Route::Game { game_id: game.game_id.clone() })) { (game.date) ", chez " (game.host) } }
+        h2 { "Historique des modifications" }
+        @if entries.is_empty() {
+            p { "Aucune modification enregistrée." }
+        }
+        @for entry in entries {
+            section .history-entry id=(entry.entry_id) {
+                h3 { (entry.change.description()) }
+                p { (format_timestamp(&entry.timestamp)) ", par " (entry.author) }
+                @let changes = entry.change.diff();
+                @if !changes.is_empty() {
+                    table {
+                        thead {
+                            tr { th { "Champ" } th { "Avant" } th { "Après" } }
+                        }
+                        tbody {
+                            @for change in &changes {
+                                tr {
+                                    td { (change.label) }
+                                    td .before { (change.before.clone().unwrap_or_default()) }
+                                    td .after { (change.after.clone().unwrap_or_default()) }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+fn game_details(game: &Game) -> Markup {
+    html! {
+        dl .game-details {
+            dt { "Date" } dd { (game.date) }
+            dt { "Chez" } dd { (game.host) }
+            dt { "Joueurs" } dd { (game.players.join(", ")) }
+            dt { "Tables" } dd { (game.tables.join(", ")) }
+            dt { "Classement" } dd { (game.ranking_method) }
+            dt { "Confirmation" } dd { (game.confirmation) }
+            dt { "État" } dd { (game.status) }
+            dt { "Archivé" } dd { (if game.archived { "oui" } else { "non" }) }
+        }
+    }
+}
+
+pub fn html_game_conflict(mine: &Game, theirs: &Game) -> Markup {
+    layout(html! {
+        h1 { "Jeu déjà modifié" }
+        p { "Quelqu'un d'autre vient de modifier ce jeu." }
+        div .conflict {
+            section {
+                h2 { "Déjà enregistré" }
+                (game_details(theirs))
+                a role="button" href=(url_for(&Route::Game { game_id: theirs.game_id.clone() })) { "Garder celui-ci" }
+            }
+            section {
+                h2 { "Vos modifications" }
+                (game_details(mine))
+                form action=(url_for(&Route::Game { game_id: theirs.game_id.clone() })) method="POST" {
+                    input type="hidden" name="date" value=(mine.date);
+                    input type="hidden" name="host" value=(mine.host);
+                    @for player_id in &mine.player_ids {
+                        input type="hidden" name="playerIds" value=(player_id);
+                    }
+                    input type="hidden" name="tables" value=(mine.tables.join("\n"));
+                    input type="hidden" name="rankingMethod" value=(mine.ranking_method);
+                    input type="hidden" name="confirmation" value=(mine.confirmation);
+                    @if mine.archived {
+                        input type="hidden" name="archived" value="on";
+                    }
+                    input type="hidden" name="version" value=(theirs.version);
+                    button type="submit" { "Le remplacer par les vôtres" }
+                }
+            }
+        }
+    })
+}
+
+pub fn html_game_locked(game: &Game) -> Markup {
+    layout(html! {
+        h1 { "Jeu verrouillé" }
+        p { "Ce jeu est terminé, donc rien ne peut y être modifié : ni ses parties, ni le jeu lui-même." }
+        p { "Pour le modifier, rouvrez-le d'abord depuis sa page." }
+        a role="button" href=(url_for(&Route::Game { game_id: game.game_id.clone() })) { "Voir le jeu" }
+    })
+}
+
 pub fn html_hand_not_found(game_id: &str, hand_id: &str) -> Markup {
     layout(html! {
         h1 { "Oops!" }
@@ -437,6 +1453,15 @@ pub fn html_hand_not_found(game_id: &str, hand_id: &str) -> Markup {
     })
 }
 
+pub fn html_table_not_found(game_id: &str, table: &str) -> Markup {
+    layout(html! {
+        h1 { "Oops!" }
+        h2 { "404 - Not Found" }
+        p { (format!("The requested table (game_id = {}, table = {}) could not be found.", game_id, table)) }
+        a href=(url_for(&Route::Game { game_id: game_id.to_string() })) { "Return to the game" }
+    })
+}
+
 pub fn html_method_not_allowed(method: &Method, path: &str) -> Markup {
     layout(html! {
         h1 { "Oops!" }
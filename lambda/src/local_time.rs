@@ -0,0 +1,52 @@
+//! Times as they're stored, RFC 3339 timestamps in UTC, and as they're shown: in Paris, where the
+//! games are played, whatever the time zone of the Lambda.
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Europe::Paris;
+
+/// Seconds since the epoch of a timestamp as stored, e.g. `2025-03-14T20:05:00Z`, with or without
+/// fractional seconds, and in UTC or at an offset (`+01:00`).
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(s).ok().map(|t| t.timestamp())
+}
+
+/// A stored timestamp as shown, in Paris: `2025-03-14 à 21:05`.  One that can't be read is shown
+/// as it is.
+pub fn format_timestamp(s: &str) -> String {
+    match DateTime::parse_from_rfc3339(s) {
+        Ok(t) => t.with_timezone(&Paris).format("%Y-%m-%d à %H:%M").to_string(),
+        Err(_) => s.to_string(),
+    }
+}
+
+/// Today's date in Paris, as `YYYY-MM-DD`.
+pub fn paris_today() -> String {
+    Utc::now().with_timezone(&Paris).format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2025-03-14T20:05:00Z"), Some(1741982700));
+        assert_eq!(parse_timestamp("2025-03-14T20:05:00.123Z"), Some(1741982700));
+        assert_eq!(parse_timestamp("2025-03-14T21:05:00+01:00"), Some(1741982700));
+        assert_eq!(parse_timestamp("2025-03-14"), None);
+        assert_eq!(parse_timestamp("not a timestamp"), None);
+    }
+
+    #[test]
+    fn test_format_timestamp_in_paris() {
+        // Winter time, an hour ahead of UTC, and late enough to be the next day in Paris.
+        assert_eq!(format_timestamp("2025-01-31T23:30:00Z"), "2025-02-01 à 00:30");
+        // Summer time starts at 01:00 UTC on the last Sunday of March, and ends on October's.
+        assert_eq!(format_timestamp("2025-03-30T00:59:00Z"), "2025-03-30 à 01:59");
+        assert_eq!(format_timestamp("2025-03-30T01:00:00Z"), "2025-03-30 à 03:00");
+        assert_eq!(format_timestamp("2025-10-26T00:59:00Z"), "2025-10-26 à 02:59");
+        assert_eq!(format_timestamp("2025-10-26T01:00:00Z"), "2025-10-26 à 02:00");
+        assert_eq!(format_timestamp("garbled"), "garbled");
+    }
+}
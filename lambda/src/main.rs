@@ -1,15 +1,24 @@
+mod chart;
 mod db;
+mod duplicates;
 mod game;
+mod history;
 mod html;
+mod local_time;
+mod names;
+mod rearrange;
 mod scoring;
 mod server;
+mod stats;
 
+use http::header::{HeaderValue, SET_COOKIE};
 use http::Method;
 use lambda_http::{run, service_fn, Body, Error as LambdaError, Request, Response as HttpResponse};
 
 use aws_sdk_dynamodb::Client;
 use aws_config::SdkConfig;
 use tokio::sync::OnceCell;
+use uuid::Uuid;
 
 static AWS_CONFIG: OnceCell<SdkConfig> = OnceCell::const_new();
 static DYNAMODB_CLIENT: OnceCell<Client> = OnceCell::const_new();
@@ -38,6 +47,13 @@ fn read_form_data(event: &Request) -> Result<Option<Vec<(String, String)>>, Lamb
     }
 }
 
+fn read_query_params(event: &Request) -> Result<Vec<(String, String)>, LambdaError> {
+    match event.uri().query() {
+        Some(query) => Ok(serde_urlencoded::from_str(query)?),
+        None => Ok(vec![]),
+    }
+}
+
 fn get_header<'a>(event: &'a Request, header_name: &str) -> Option<&'a str> {
     event.headers()
         .get(header_name)?
@@ -45,6 +61,23 @@ fn get_header<'a>(event: &'a Request, header_name: &str) -> Option<&'a str> {
         .ok()
 }
 
+/// The cookie that tells devices apart, e.g. so that a hand can't be confirmed on the device it
+/// was entered on.
+const DEVICE_COOKIE: &str = "device";
+
+/// The ID of the device a request comes from, and whether it's new (i.e. it didn't send one).
+fn device_id(event: &Request) -> (String, bool) {
+    let cookie = get_header(event, "cookie").and_then(|cookies| cookies
+        .split(';')
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, value)| *name == DEVICE_COOKIE && !value.is_empty())
+        .map(|(_, value)| value.to_string()));
+    match cookie {
+        Some(device) => (device, false),
+        None => (Uuid::new_v4().to_string(), true),
+    }
+}
+
 async fn lambda_handler(event: Request) -> Result<HttpResponse<Body>, LambdaError> {
     let config = AWS_CONFIG.get_or_init(|| async { aws_config::load_from_env().await }).await;
     let client = DYNAMODB_CLIENT.get_or_init(|| async { Client::new(config) }).await;
@@ -53,15 +86,24 @@ async fn lambda_handler(event: Request) -> Result<HttpResponse<Body>, LambdaErro
         .or_else(|| get_header(&event, "host"))
         .unwrap_or("localhost:3000");
 
+    let (device, new_device) = device_id(&event);
     let response = server::handler::handle(
         client,
         host,
         event.method(),
         event.uri().path(),
+        &read_query_params(&event)?,
         &read_form_data(&event)?,
+        get_header(&event, "user-agent"),
+        &device,
     ).await?;
 
-    Ok(server::responses::render(response)?)
+    let mut response = server::responses::render(response)?;
+    if new_device {
+        let cookie = format!("{}={}; Path=/; Max-Age=31536000; HttpOnly; Secure; SameSite=Lax", DEVICE_COOKIE, device);
+        response.headers_mut().append(SET_COOKIE, HeaderValue::from_str(&cookie)?);
+    }
+    Ok(response)
 }
 
 #[tokio::main]
@@ -72,5 +114,11 @@ async fn main() -> Result<(), LambdaError> {
         .without_time()
         .init();
 
+    // Migrations are run by hand, against the same tables as the Lambda.
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        let config = aws_config::load_from_env().await;
+        return Ok(db::migrations::run(&Client::new(&config)).await?);
+    }
+
     run(service_fn(lambda_handler)).await
 }
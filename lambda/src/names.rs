@@ -0,0 +1,84 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// A player or table name as it should be stored: NFC, trimmed, with runs of whitespace collapsed
+/// to a single space.
+pub fn normalise_name(name: &str) -> String {
+    name.nfc()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What two names must share to be considered the same: the normalised name, ignoring case and
+/// accents, so that "Sébastien", "sebastien " and "SÉBASTIEN" are all one person.
+pub fn name_key(name: &str) -> String {
+    normalise_name(name)
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+pub fn same_name(a: &str, b: &str) -> bool {
+    name_key(a) == name_key(b)
+}
+
+/// The first pair of `names` that are the same name written differently, if any.
+pub fn find_near_duplicate(names: &[String]) -> Option<(&String, &String)> {
+    names.iter().enumerate().find_map(|(i, name)| {
+        names[..i].iter().find(|earlier| same_name(earlier, name)).map(|earlier| (earlier, name))
+    })
+}
+
+/// The names renamed between `old` and `new`, as (old, new) pairs: the names that are no longer in
+/// the list, each paired with the name added in its place, in the order they appear.  Moving names
+/// around doesn't rename them, so a list can be reordered and have a typo fixed in one edit.
+pub fn renamed_names(old: &[String], new: &[String]) -> Vec<(String, String)> {
+    let removed = old.iter().filter(|o| !new.contains(o));
+    let added = new.iter().filter(|n| !old.contains(n));
+    removed.zip(added).map(|(o, n)| (o.clone(), n.clone())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalise_name() {
+        assert_eq!(normalise_name("  Jean   Pierre \t"), "Jean Pierre");
+        // "e" followed by a combining acute accent composes to "é"
+        assert_eq!(normalise_name("Se\u{301}bastien"), "Sébastien");
+    }
+
+    #[test]
+    fn test_same_name() {
+        assert!(same_name("Sébastien", "sebastien "));
+        assert!(same_name("SÉBASTIEN", "Se\u{301}bastien"));
+        assert!(same_name("Table  A", "table a"));
+        assert!(!same_name("Seb", "Sébastien"));
+    }
+
+    #[test]
+    fn test_find_near_duplicate() {
+        let names = vec!["Marie".to_string(), "Paul".to_string(), "marie".to_string()];
+        assert_eq!(find_near_duplicate(&names), Some((&names[0], &names[2])));
+        assert_eq!(find_near_duplicate(&names[..2]), None);
+    }
+
+    #[test]
+    fn test_renamed_names() {
+        let old: Vec<String> = ["Atout", "Exuse", "Petit"].iter().map(|s| s.to_string()).collect();
+        let fixed: Vec<String> = ["Atout", "Excuse", "Petit"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(renamed_names(&old, &fixed), vec![("Exuse".to_string(), "Excuse".to_string())]);
+
+        // Reordering or adding tables isn't renaming them.
+        let reordered: Vec<String> = ["Petit", "Atout", "Exuse", "Pli"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(renamed_names(&old, &reordered), vec![]);
+
+        // Even when a table is renamed in the same edit.
+        let reordered_and_fixed: Vec<String> = ["Petit", "Excuse", "Atout"].iter().map(|s| s.to_string()).collect();
+        assert_eq!(renamed_names(&old, &reordered_and_fixed), vec![("Exuse".to_string(), "Excuse".to_string())]);
+    }
+}
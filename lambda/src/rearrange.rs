@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use crate::db::HandMove;
+use crate::game::{CompletedHand, Game};
+use crate::names::same_name;
+
+/// Merges `source` into `target`: the target gets the source's players and tables, and all its
+/// hands.  Tables that are the same name written differently are merged into the target's.
+///
+/// Source hands keep their numbers unless one of them is already taken at the target's table, in
+/// which case all the source's hands at that table follow on after the target's.
+///
+/// Players are merged by their registry ID, so both games must have been migrated to the registry.
+pub fn merge_games(source: &Game, source_hands: &[CompletedHand], target: &Game, target_hands: &[CompletedHand]) -> Result<(Game, Vec<HandMove>), String> {
+    if let Some(game) = [source, target].into_iter().find(|g| g.needs_migration()) {
+        return Err(not_migrated(game));
+    }
+
+    let mut game = target.clone();
+    for (player_id, player) in source.player_ids.iter().zip(source.players.iter()) {
+        if !game.player_ids.contains(player_id) {
+            game.player_ids.push(player_id.clone());
+            game.players.push(player.clone());
+        }
+    }
+
+    let mut tables: HashMap<&String, String> = HashMap::new();
+    for table in &source.tables {
+        match game.tables.iter().find(|t| same_name(t, table)) {
+            Some(t) => tables.insert(table, t.clone()),
+            None => {
+                game.tables.push(table.clone());
+                tables.insert(table, table.clone())
+            },
+        };
+    }
+
+    let mut hands: Vec<&CompletedHand> = source_hands.iter().collect();
+    hands.sort_by_key(|h| h.hand_number);
+    let mut renumbered: HashMap<String, i32> = HashMap::new();
+    let moves = hands
+        .into_iter()
+        .map(|hand| {
+            let table = tables.get(&hand.table).cloned().unwrap_or(hand.table.clone());
+            let target_numbers: Vec<i32> = target_hands.iter().filter(|h| h.table == table).map(|h| h.hand_number).collect();
+            let collides = source_hands.iter()
+                .any(|h| h.table == hand.table && target_numbers.contains(&h.hand_number));
+            let hand_number = if collides {
+                let last = renumbered.entry(table.clone()).or_insert(target_numbers.iter().copied().max().unwrap_or(0));
+                *last += 1;
+                *last
+            } else {
+                hand.hand_number
+            };
+            HandMove {
+                from_game_id: source.game_id.clone(),
+                from_hand_id: hand.hand_id(),
+                to_game_id: game.game_id.clone(),
+                hand: CompletedHand { table, hand_number, ..hand.clone() },
+            }
+        })
+        .collect();
+    Ok((game, moves))
+}
+
+/// Splits `table` off `game` into a new game with the same date, host and rules, made up of the
+/// players who played at it (or all the game's players, if no one has yet).  Its hands keep their
+/// numbers.  Returns the game without the table, the new game, and the hands to move.
+///
+/// The new game's players are picked out by their registry IDs, so `game` must have been migrated
+/// to the registry.
+pub fn split_table(game: &Game, hands: &[CompletedHand], table: &str, new_game_id: String) -> Result<(Game, Game, Vec<HandMove>), String> {
+    if game.needs_migration() {
+        return Err(not_migrated(game));
+    }
+
+    let table_hands: Vec<&CompletedHand> = hands.iter().filter(|h| h.table == table).collect();
+
+    let mut new_game = Game {
+        game_id: new_game_id,
+        tables: vec![table.to_string()],
+        archived: false,
+        version: 0,
+        ..game.clone()
+    };
+    if !table_hands.is_empty() {
+        let played = |name: &String| table_hands.iter().any(|h| h.players.contains(name));
+        new_game.player_ids = game.player_ids.iter().zip(game.players.iter()).filter(|(_, p)| played(p)).map(|(id, _)| id.clone()).collect();
+        new_game.players = game.players.iter().filter(|p| played(p)).cloned().collect();
+    }
+
+    let remaining = Game {
+        tables: game.tables.iter().filter(|t| *t != table).cloned().collect(),
+        ..game.clone()
+    };
+
+    let moves = table_hands
+        .into_iter()
+        .map(|hand| HandMove {
+            from_game_id: game.game_id.clone(),
+            from_hand_id: hand.hand_id(),
+            to_game_id: new_game.game_id.clone(),
+            hand: hand.clone(),
+        })
+        .collect();
+    Ok((remaining, new_game, moves))
+}
+
+fn not_migrated(game: &Game) -> String {
+    format!("Game {} hasn't been moved to the player registry yet: run the migrations first", game.game_id)
+}
+
+/// Renumbers `table`'s hands 1..N in the order they were played, closing any gaps.  Only the hands
+/// whose number changes are moved.
+///
+/// The order they were played is the order they were entered in, if every hand records when that
+/// was.  Otherwise (some were saved before it was recorded) their numbers are kept in order.
+pub fn renumber_table(game: &Game, hands: &[CompletedHand], table: &str) -> Vec<HandMove> {
+    let mut table_hands: Vec<&CompletedHand> = hands.iter().filter(|h| h.table == table).collect();
+    if table_hands.iter().all(|h| h.created_at.is_some()) {
+        table_hands.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.hand_number.cmp(&b.hand_number)));
+    } else {
+        table_hands.sort_by_key(|h| h.hand_number);
+    }
+    table_hands
+        .into_iter()
+        .zip(1..)
+        .filter(|(hand, hand_number)| hand.hand_number != *hand_number)
+        .map(|(hand, hand_number)| HandMove {
+            from_game_id: game.game_id.clone(),
+            from_hand_id: hand.hand_id(),
+            to_game_id: game.game_id.clone(),
+            hand: CompletedHand { hand_number, ..hand.clone() },
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Confirmation, GameStatus, RankingMethod};
+
+    fn game(game_id: &str, players: &[&str], tables: &[&str]) -> Game {
+        Game {
+            game_id: game_id.to_string(),
+            date: "2025-03-14".to_string(),
+            host: "Marie".to_string(),
+            players: players.iter().map(|p| p.to_string()).collect(),
+            player_ids: players.iter().map(|p| format!("id-{}", p)).collect(),
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            ranking_method: RankingMethod::Total,
+            confirmation: Confirmation::Off,
+            status: GameStatus::InProgress,
+            final_standings: vec![],
+            archived: false,
+            version: 0,
+        }
+    }
+
+    fn hand(table: &str, hand_number: i32, players: &[&str]) -> CompletedHand {
+        CompletedHand { table: table.to_string(), hand_number, ..CompletedHand::test(players, players[0], None) }
+    }
+
+    #[test]
+    fn test_merge_games() {
+        let target = game("target", &["Alice", "Bob", "Charlie"], &["Atout"]);
+        let source = game("source", &["Bob", "David"], &["atout", "Excuse"]);
+        let target_hands = vec![hand("Atout", 1, &["Alice", "Bob", "Charlie"]), hand("Atout", 2, &["Alice", "Bob", "Charlie"])];
+        let source_hands = vec![
+            hand("atout", 2, &["Bob", "David", "Alice"]),
+            hand("atout", 1, &["Bob", "David", "Alice"]),
+            hand("Excuse", 1, &["David", "Bob", "Charlie"]),
+        ];
+
+        let (merged, moves) = merge_games(&source, &source_hands, &target, &target_hands).unwrap();
+        assert_eq!(merged.players, vec!["Alice", "Bob", "Charlie", "David"]);
+        assert_eq!(merged.player_ids[3], "id-David");
+        assert_eq!(merged.tables, vec!["Atout", "Excuse"]);
+
+        let moved: Vec<(String, String)> = moves.iter().map(|m| (m.from_hand_id.clone(), m.hand.hand_id())).collect();
+        assert_eq!(moved, vec![
+            ("01-atout".to_string(), "03-Atout".to_string()),
+            ("01-Excuse".to_string(), "01-Excuse".to_string()),
+            ("02-atout".to_string(), "04-Atout".to_string()),
+        ]);
+        assert!(moves.iter().all(|m| m.from_game_id == "source" && m.to_game_id == "target"));
+    }
+
+    #[test]
+    fn test_merge_games_not_migrated() {
+        let target = game("target", &["Alice", "Bob"], &["Atout"]);
+        let legacy = Game { player_ids: vec![], ..game("legacy", &["Charlie", "David"], &["Excuse"]) };
+        let legacy_hands = vec![hand("Excuse", 1, &["Charlie", "David", "Alice"])];
+
+        // Their players would be dropped, so neither way round is merged.
+        assert!(merge_games(&legacy, &legacy_hands, &target, &[]).is_err());
+        assert!(merge_games(&target, &[], &legacy, &legacy_hands).is_err());
+        assert!(split_table(&legacy, &legacy_hands, "Excuse", "new".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_split_table() {
+        let original = game("original", &["Alice", "Bob", "Charlie", "David"], &["Atout", "Excuse"]);
+        let hands = vec![hand("Atout", 1, &["Alice", "Bob", "Charlie"]), hand("Excuse", 1, &["David", "Bob", "Charlie"])];
+
+        let (remaining, new_game, moves) = split_table(&original, &hands, "Excuse", "new".to_string()).unwrap();
+        assert_eq!(remaining.tables, vec!["Atout"]);
+        assert_eq!(new_game.tables, vec!["Excuse"]);
+        assert_eq!(new_game.players, vec!["Bob", "Charlie", "David"]);
+        assert_eq!(new_game.player_ids, vec!["id-Bob", "id-Charlie", "id-David"]);
+        assert_eq!(moves.len(), 1);
+        assert_eq!((moves[0].to_game_id.as_str(), moves[0].hand.hand_id()), ("new", "01-Excuse".to_string()));
+    }
+
+    #[test]
+    fn test_renumber_table() {
+        let original = game("original", &["Alice", "Bob", "Charlie"], &["Atout", "Excuse"]);
+        let players = ["Alice", "Bob", "Charlie"];
+        let hands = vec![hand("Atout", 4, &players), hand("Atout", 1, &players), hand("Atout", 3, &players), hand("Excuse", 2, &players)];
+
+        let moves = renumber_table(&original, &hands, "Atout");
+        let moved: Vec<(String, String)> = moves.iter().map(|m| (m.from_hand_id.clone(), m.hand.hand_id())).collect();
+        assert_eq!(moved, vec![
+            ("03-Atout".to_string(), "02-Atout".to_string()),
+            ("04-Atout".to_string(), "03-Atout".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_renumber_table_in_the_order_entered() {
+        let original = game("original", &["Alice", "Bob", "Charlie"], &["Atout"]);
+        let players = ["Alice", "Bob", "Charlie"];
+        let entered = |hand_number, created_at: &str| CompletedHand {
+            created_at: Some(created_at.to_string()),
+            ..hand("Atout", hand_number, &players)
+        };
+        // #3 was entered as #1 by mistake, and #1 fixed up afterwards.
+        let hands = vec![
+            entered(1, "2025-03-14T21:30:00Z"),
+            entered(2, "2025-03-14T20:10:00Z"),
+            entered(3, "2025-03-14T20:00:00Z"),
+        ];
+
+        let moves = renumber_table(&original, &hands, "Atout");
+        let moved: Vec<(String, String)> = moves.iter().map(|m| (m.from_hand_id.clone(), m.hand.hand_id())).collect();
+        assert_eq!(moved, vec![
+            ("03-Atout".to_string(), "01-Atout".to_string()),
+            ("01-Atout".to_string(), "03-Atout".to_string()),
+        ]);
+
+        // Without an entry time for every hand, the numbers are kept in order.
+        let hands = vec![hand("Atout", 1, &players), entered(2, "2025-03-14T20:10:00Z"), entered(3, "2025-03-14T20:00:00Z")];
+        assert!(renumber_table(&original, &hands, "Atout").is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::game::{Bid, Chelem, CompletedHand, Poignée};
+use crate::game::{Bid, Chelem, CompletedHand, Game, Poignée, RankingMethod};
 
 impl Bid {
     pub fn multiplier(&self) -> i32 {
@@ -75,12 +75,16 @@ pub fn score(hand: &CompletedHand) -> Result<HashMap<String, i32>, String> {
     Ok(scores)
 }
 
-pub fn score_hands(hands: Vec<CompletedHand>) -> Result<(Vec<(CompletedHand, HashMap<String, i32>)>, HashMap<String, i32>, HashMap<String, i32>), String> {
+/// Each hand with its scores, the total score for each player, and the number of hands each player played.
+pub type ScoredHands = (Vec<(CompletedHand, HashMap<String, i32>)>, HashMap<String, i32>, HashMap<String, i32>);
+
+/// Scores the hands that aren't in the trash.
+pub fn score_hands(hands: Vec<CompletedHand>) -> Result<ScoredHands, String> {
     let mut hands_with_scores = vec![];
     let mut total_scores = HashMap::new();
     let mut player_hand_count = HashMap::new();
 
-    for hand in hands {
+    for hand in hands.into_iter().filter(|h| h.deleted_at.is_none()) {
         match score(&hand) {
             Ok(scores) => {
                 hands_with_scores.push((hand, scores.clone()));
@@ -96,6 +100,176 @@ pub fn score_hands(hands: Vec<CompletedHand>) -> Result<(Vec<(CompletedHand, Has
     Ok((hands_with_scores, total_scores, player_hand_count))
 }
 
+/// What a game's ranking is worked out from.  They're kept up to date on the game as its hands
+/// change, so that the list of games can show each game's leader without scoring its hands.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tallies {
+    pub totals: HashMap<String, i32>,
+    pub hand_counts: HashMap<String, i32>,
+    /// Each player's total, with each hand's score divided by the number of opponents at its table.
+    pub weighted_totals: HashMap<String, f64>,
+}
+
+impl Tallies {
+    fn of_scored(hands_with_scores: &[(CompletedHand, HashMap<String, i32>)]) -> Tallies {
+        let mut tallies = Tallies::default();
+        for (hand, scores) in hands_with_scores {
+            tallies.add(hand, scores, 1);
+        }
+        tallies
+    }
+
+    /// Counts `hand`, which scored `scores`, once more (`sign` 1) or once less (`sign` -1).
+    pub fn add(&mut self, hand: &CompletedHand, scores: &HashMap<String, i32>, sign: i32) {
+        let opponents = (hand.players.len() - 1) as f64;
+        for (player, score) in scores {
+            *self.totals.entry(player.clone()).or_insert(0) += sign * score;
+            *self.hand_counts.entry(player.clone()).or_insert(0) += sign;
+            *self.weighted_totals.entry(player.clone()).or_insert(0.0) += (sign * score) as f64 / opponents;
+        }
+    }
+
+    /// The same tallies, with each player renamed by `f`.
+    pub fn map_players<F: Fn(String) -> String>(self, f: F) -> Tallies {
+        Tallies {
+            totals: self.totals.into_iter().map(|(player, total)| (f(player), total)).collect(),
+            hand_counts: self.hand_counts.into_iter().map(|(player, hand_count)| (f(player), hand_count)).collect(),
+            weighted_totals: self.weighted_totals.into_iter().map(|(player, total)| (f(player), total)).collect(),
+        }
+    }
+
+    /// Ranks every player who has played a hand, best first, by the given method.
+    ///
+    /// `AveragePerHand` stops players who played more hands from piling up bigger swings.
+    /// `WeightedByTableSize` additionally divides each hand's score by the number of opponents at
+    /// the table (players - 1), so that hands at four- and five-player tables count for the same.
+    pub fn ranking(&self, method: &RankingMethod) -> Vec<(String, f64)> {
+        let mut ranking: Vec<(String, f64)> = self.hand_counts
+            .iter()
+            .filter(|(_, hand_count)| **hand_count > 0)
+            .map(|(player, hand_count)| {
+                let total = self.totals.get(player).copied().unwrap_or(0) as f64;
+                let weighted_total = self.weighted_totals.get(player).copied().unwrap_or(0.0);
+                let score = match method {
+                    RankingMethod::Total => total,
+                    RankingMethod::AveragePerHand => total / *hand_count as f64,
+                    RankingMethod::WeightedByTableSize => weighted_total / *hand_count as f64,
+                };
+                (player.clone(), score)
+            })
+            .collect();
+        ranking.sort_by(|(p1, s1), (p2, s2)| s2.total_cmp(s1).then_with(|| p1.cmp(p2)));
+        ranking
+    }
+}
+
+/// Ranks every player who has played one of `hands_with_scores`, best first, by the given method.
+pub fn rank_players(method: &RankingMethod, hands_with_scores: &[(CompletedHand, HashMap<String, i32>)]) -> Vec<(String, f64)> {
+    Tallies::of_scored(hands_with_scores).ranking(method)
+}
+
+/// Each player's cumulative score after each hand, in the same order as `hands_with_scores`.
+pub fn running_totals(hands_with_scores: &[(CompletedHand, HashMap<String, i32>)]) -> Vec<HashMap<String, i32>> {
+    let mut totals: HashMap<String, i32> = HashMap::new();
+    hands_with_scores
+        .iter()
+        .map(|(_, scores)| {
+            for (player, score) in scores {
+                *totals.entry(player.clone()).or_insert(0) += score;
+            }
+            totals.clone()
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    pub player: String,
+    pub total: i32,
+    /// 1-based, with tied players sharing a position (1, 2, 2, 4, ...).
+    pub position: usize,
+    pub tied: bool,
+    pub gap_to_leader: i32,
+    /// How many places the player moved up (positive) or down (negative) with the latest hand.
+    pub movement: i32,
+}
+
+fn positions(totals: &HashMap<String, i32>) -> Vec<(String, i32, usize)> {
+    let mut sorted: Vec<(String, i32)> = totals.iter().map(|(p, t)| (p.clone(), *t)).collect();
+    sorted.sort_by(|(p1, t1), (p2, t2)| t2.cmp(t1).then_with(|| p1.cmp(p2)));
+    sorted
+        .iter()
+        .map(|(player, total)| {
+            let position = 1 + sorted.iter().filter(|(_, t)| t > total).count();
+            (player.clone(), *total, position)
+        })
+        .collect()
+}
+
+/// Players ordered by total score, with their position and how it changed since the previous hand.
+pub fn leaderboard(running_totals: &[HashMap<String, i32>]) -> Vec<LeaderboardEntry> {
+    let current = match running_totals.last() {
+        Some(totals) => positions(totals),
+        None => return vec![],
+    };
+    let previous: HashMap<String, usize> = match running_totals.len() {
+        n if n >= 2 => positions(&running_totals[n - 2]).into_iter().map(|(p, _, pos)| (p, pos)).collect(),
+        _ => HashMap::new(),
+    };
+    let leader_total = current.first().map(|(_, t, _)| *t).unwrap_or(0);
+
+    current
+        .iter()
+        .map(|(player, total, position)| LeaderboardEntry {
+            player: player.clone(),
+            total: *total,
+            position: *position,
+            tied: current.iter().filter(|(_, _, p)| p == position).count() > 1,
+            gap_to_leader: leader_total - total,
+            movement: previous.get(player).map(|prev| *prev as i32 - *position as i32).unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Everything the game and table pages show about a set of hands' scores.
+#[derive(Debug)]
+pub struct GameSummary {
+    pub hands_with_scores: Vec<(CompletedHand, HashMap<String, i32>)>,
+    pub total_scores: HashMap<String, i32>,
+    pub player_hand_count: HashMap<String, i32>,
+    pub running_totals: Vec<HashMap<String, i32>>,
+    pub leaderboard: Vec<LeaderboardEntry>,
+    pub ranking: Vec<(String, f64)>,
+}
+
+pub fn summarise(ranking_method: &RankingMethod, hands: Vec<CompletedHand>) -> Result<GameSummary, String> {
+    let (hands_with_scores, total_scores, player_hand_count) = score_hands(hands)?;
+    let running_totals = running_totals(&hands_with_scores);
+    let leaderboard = leaderboard(&running_totals);
+    let ranking = rank_players(ranking_method, &hands_with_scores);
+    Ok(GameSummary { hands_with_scores, total_scores, player_hand_count, running_totals, leaderboard, ranking })
+}
+
+/// What the list of games shows about each one.
+#[derive(Debug)]
+pub struct GameListing {
+    pub game: Game,
+    pub hand_count: i32,
+    /// The first in the game's ranking, if anyone has played a hand that counts.
+    pub leader: Option<String>,
+}
+
+impl GameListing {
+    pub fn new(game: Game, hand_count: i32, tallies: &Tallies) -> GameListing {
+        let leader = if game.is_finished() {
+            game.final_standings.first().map(|(player, _)| player.clone())
+        } else {
+            tallies.ranking(&game.ranking_method).first().map(|(player, _)| player.clone())
+        };
+        GameListing { game, hand_count, leader }
+    }
+}
+
 // let total_scores: HashMap<String, i32> = HashMap::new();
 //                     let hands_with_scores = hands
 //                         .into_iter()
@@ -128,18 +302,8 @@ mod tests {
     fn test_four_player_hand_won() {
         let players = create_test_players();
         let hand = CompletedHand {
-            table: "Atout".to_string(),
-            hand_number: 1,
-            players: players.clone(),
-            bid: Bid::Petite,
-            bidder: "Alice".to_string(),
-            partner: None,
-            defence: vec!["Bob".to_string(), "Charlie".to_string(), "David".to_string()],
-            won: true,
             won_or_lost_by: 10,
-            petit_au_bout: false,
-            poignee: Poignée::Aucune,
-            chelem: Chelem::Aucun,
+            ..CompletedHand::test(&players, "Alice", None)
         };
 
         let scores = score(&hand).unwrap();
@@ -155,18 +319,11 @@ mod tests {
     fn test_five_player_hand_with_partner_won() {
         let players = create_test_players_five();
         let hand = CompletedHand {
-            table: "Atout".to_string(),
-            hand_number: 1,
-            players,
             bid: Bid::Garde,
-            bidder: "Alice".to_string(),
-            partner: Some("Bob".to_string()),
-            defence: vec!["Charlie".to_string(), "David".to_string(), "Eve".to_string()],
-            won: true,
             won_or_lost_by: 15,
             petit_au_bout: true,
             poignee: Poignée::Simple,
-            chelem: Chelem::Aucun,
+            ..CompletedHand::test(&players, "Alice", Some("Bob"))
         };
 
         let scores = score(&hand).unwrap();
@@ -183,18 +340,10 @@ mod tests {
     fn test_five_player_hand_bidder_alone_lost() {
         let players = create_test_players_five();
         let hand = CompletedHand {
-            table: "Atout".to_string(),
-            hand_number: 1,
-            players,
             bid: Bid::GardeSans,
-            bidder: "Alice".to_string(),
-            partner: None,
-            defence: vec!["Bob".to_string(), "Charlie".to_string(), "David".to_string(), "Eve".to_string()],
             won: false,
             won_or_lost_by: 20,
-            petit_au_bout: false,
-            poignee: Poignée::Aucune,
-            chelem: Chelem::Aucun,
+            ..CompletedHand::test(&players, "Alice", None)
         };
 
         let scores = score(&hand).unwrap();
@@ -211,18 +360,12 @@ mod tests {
     fn test_chelem_and_poignee_scoring() {
         let players = create_test_players();
         let hand = CompletedHand {
-            table: "Atout".to_string(),
-            hand_number: 1,
-            players: players.clone(),
             bid: Bid::GardeContre,
-            bidder: "Alice".to_string(),
-            partner: None,
-            defence: vec!["Bob".to_string(), "Charlie".to_string(), "David".to_string()],
-            won: true,
             won_or_lost_by: 30,
             petit_au_bout: true,
             poignee: Poignée::Double,
             chelem: Chelem::Annoncé,
+            ..CompletedHand::test(&players, "Alice", None)
         };
 
         let scores = score(&hand).unwrap();
@@ -236,22 +379,113 @@ mod tests {
 
     #[test]
     fn test_invalid_hand_configuration() {
-        let players = vec!["Alice".to_string(), "Bob".to_string(), "Charlie".to_string()]; // Only 3 players
+        let players = ["Alice", "Bob", "Charlie"]; // Only 3 players
         let hand = CompletedHand {
-            table: "Atout".to_string(),
-            hand_number: 1,
-            players,
-            bid: Bid::Petite,
-            bidder: "Alice".to_string(),
-            partner: None,
-            defence: vec!["Bob".to_string(), "Charlie".to_string()],
-            won: true,
             won_or_lost_by: 10,
-            petit_au_bout: false,
-            poignee: Poignée::Aucune,
-            chelem: Chelem::Aucun,
+            ..CompletedHand::test(&players, "Alice", None)
         };
 
         assert!(score(&hand).is_err());
     }
+
+    fn create_test_hand(players: Vec<&str>, bidder: &str, partner: Option<&str>, won: bool) -> CompletedHand {
+        CompletedHand { won, ..CompletedHand::test(&players, bidder, partner) }
+    }
+
+    #[test]
+    fn test_score_hands_skips_deleted_hands() {
+        let mut deleted = create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Bob", None, true);
+        deleted.deleted_at = Some("2025-03-14T21:30:00Z".to_string());
+        let hands = vec![create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Alice", None, true), deleted];
+        let (hands_with_scores, total_scores, player_hand_count) = score_hands(hands).unwrap();
+        assert_eq!(hands_with_scores.len(), 1);
+        assert_eq!(total_scores["Bob"], -25);
+        assert_eq!(player_hand_count["Bob"], 1);
+    }
+
+    #[test]
+    fn test_rank_players_average_per_hand() {
+        // Alice wins once at a four-player table (+75), then sits out while Bob wins twice.
+        let hands = vec![
+            create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Alice", None, true),
+            create_test_hand(vec!["Bob", "Charlie", "David", "Eve"], "Bob", None, true),
+            create_test_hand(vec!["Bob", "Charlie", "David", "Eve"], "Bob", None, true),
+        ];
+        let (hands_with_scores, _, _) = score_hands(hands).unwrap();
+
+        let by_total = rank_players(&RankingMethod::Total, &hands_with_scores);
+        assert_eq!(by_total[0], ("Bob".to_string(), 125.0)); // -25 + 75 + 75
+        assert_eq!(by_total[1], ("Alice".to_string(), 75.0));
+
+        let by_average = rank_players(&RankingMethod::AveragePerHand, &hands_with_scores);
+        assert_eq!(by_average[0], ("Alice".to_string(), 75.0));
+        assert_eq!(by_average[1], ("Bob".to_string(), 125.0 / 3.0));
+    }
+
+    #[test]
+    fn test_rank_players_weighted_by_table_size() {
+        // Alice wins alone at a four-player table, Eve wins alone at a five-player table: both
+        // took the same risk, but Eve's raw score is bigger.
+        let hands = vec![
+            create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Alice", None, true),
+            create_test_hand(vec!["Eve", "Frank", "Grace", "Heidi", "Ivan"], "Eve", None, true),
+        ];
+        let (hands_with_scores, _, _) = score_hands(hands).unwrap();
+
+        let by_average = rank_players(&RankingMethod::AveragePerHand, &hands_with_scores);
+        assert_eq!(by_average[0], ("Eve".to_string(), 100.0));
+        assert_eq!(by_average[1], ("Alice".to_string(), 75.0));
+
+        let weighted = rank_players(&RankingMethod::WeightedByTableSize, &hands_with_scores);
+        assert_eq!(weighted[0], ("Alice".to_string(), 25.0));
+        assert_eq!(weighted[1], ("Eve".to_string(), 25.0));
+        assert_eq!(weighted[2].1, -25.0 / 4.0); // five-player defence
+        assert_eq!(weighted.last().unwrap().1, -25.0 / 3.0); // four-player defence
+    }
+
+    #[test]
+    fn test_tallies_take_back_a_hand() {
+        let hands = vec![
+            create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Alice", None, true),
+            create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Bob", None, true),
+        ];
+        let (hands_with_scores, _, _) = score_hands(hands).unwrap();
+        let mut tallies = Tallies::of_scored(&hands_with_scores);
+
+        // Moving the second hand out of the game leaves Alice's win as the only hand.
+        let (moved, scores) = &hands_with_scores[1];
+        tallies.add(moved, scores, -1);
+        assert_eq!(tallies.ranking(&RankingMethod::Total)[0], ("Alice".to_string(), 75.0));
+        assert_eq!(tallies.ranking(&RankingMethod::Total), rank_players(&RankingMethod::Total, &hands_with_scores[..1]));
+
+        let (first, scores) = &hands_with_scores[0];
+        tallies.add(first, scores, -1);
+        assert!(tallies.ranking(&RankingMethod::AveragePerHand).is_empty());
+    }
+
+    #[test]
+    fn test_leaderboard_positions_ties_and_movement() {
+        let hands = vec![
+            // Alice +75, Bob/Charlie/David -25
+            create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Alice", None, true),
+            // Bob +75, Alice/Charlie/David -25: Alice and Bob tied on 50
+            create_test_hand(vec!["Alice", "Bob", "Charlie", "David"], "Bob", None, true),
+        ];
+        let (hands_with_scores, _, _) = score_hands(hands).unwrap();
+        let totals = running_totals(&hands_with_scores);
+        assert_eq!(totals[0]["Alice"], 75);
+        assert_eq!(totals[1]["Alice"], 50);
+        assert_eq!(totals[1]["Bob"], 50);
+
+        let board = leaderboard(&totals);
+        let summary: Vec<(&str, i32, usize, bool, i32, i32)> = board.iter()
+            .map(|e| (e.player.as_str(), e.total, e.position, e.tied, e.gap_to_leader, e.movement))
+            .collect();
+        assert_eq!(summary, vec![
+            ("Alice", 50, 1, true, 0, 0),
+            ("Bob", 50, 1, true, 0, 1),
+            ("Charlie", -50, 3, true, 100, -1),
+            ("David", -50, 3, true, 100, -1),
+        ]);
+    }
 }
\ No newline at end of file
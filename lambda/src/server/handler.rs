@@ -1,34 +1,251 @@
+use std::time::SystemTime;
+
+use aws_sdk_dynamodb::primitives::{DateTime, DateTimeFormat as Format};
 use http::Method;
 use lambda_http::Error as LambdaError;
 use uuid::Uuid;
 
-use crate::db::{delete_hand, get_game, get_hands, put_game, put_hand};
+use crate::db::{delete_game, delete_submission, get_deleted_hands, get_game, get_hands, get_hands_and_trash, get_history, get_player, get_players, get_players_by_id, get_players_cached, list_games, put_player, put_submission, reserve_submission, DbError, HandMove, Submission, Transaction};
+use crate::duplicates::is_likely_duplicate;
+use crate::history::author_label;
+use crate::local_time::paris_today;
+use crate::game::{CompletedHand, Game, GameSearch, GameStatus, Player};
+use crate::names::{find_near_duplicate, normalise_name, renamed_names, same_name};
+use crate::rearrange;
 use crate::scoring;
+use crate::stats;
 use crate::server::http_utils;
 use crate::server::responses::Response;
-use crate::server::routes::{match_route, Route};
+use crate::server::routes::{match_route, url_for, Route};
+
+const GAMES_PER_PAGE: i32 = 20;
+
+/// The most registry players a search for a player can match, each of which adds to the query.
+const MAX_SEARCH_PLAYERS: usize = 10;
 
-fn is_delete(form_data: &Vec<(String, String)>) -> bool {
+fn is_delete(form_data: &[(String, String)]) -> bool {
     form_data.iter().any(|(k, v)| k == "_method" && v == "DELETE")
 }
 
+/// Forms may ask to come back to a page other than the game page (e.g. the table page they were
+/// submitted from), as long as it's a page of the same game.
+fn redirect_after_save(game: Game, form_data: &[(String, String)]) -> Response {
+    let game_url = url_for(&Route::Game { game_id: game.game_id.clone() });
+    match form_data.iter().find(|(k, _)| k == "returnTo") {
+        Some((_, location)) if location.starts_with(&format!("{}/", game_url)) => {
+            Response::Redirect { location: location.clone() }
+        },
+        _ => Response::RedirectToGame { game },
+    }
+}
+
+/// The registry players a game form needs: those it lists, unless it adds or renames players,
+/// which are checked against the whole registry.
+async fn form_registry(client: &aws_sdk_dynamodb::Client, form_data: &[(String, String)]) -> Result<Vec<Player>, DbError> {
+    let player_ids: Vec<String> = form_data.iter().filter(|(k, _)| k == "playerIds").map(|(_, v)| v.clone()).collect();
+    let players = get_players_by_id(client, &player_ids).await?;
+    let adds = form_data.iter().any(|(k, v)| k == "newPlayers" && !v.trim().is_empty());
+    let renames = form_data.iter().any(|(k, v)| {
+        let name = normalise_name(v);
+        k.strip_prefix("playerName.")
+            .and_then(|id| players.get(id))
+            .is_some_and(|p| !name.is_empty() && name != p.display_name)
+    });
+    if adds || renames {
+        get_players(client).await
+    } else {
+        Ok(players.into_values().collect())
+    }
+}
+
+/// Why `game` can't replace `old_game`, if it drops a player or table that `hands` (as loaded for
+/// `old_game`) refer to.  Renamed tables aren't dropped: their hands move with them.
+fn check_removals(old_game: &Game, game: &Game, hands: &[CompletedHand], renamed_tables: &[(String, String)]) -> Option<String> {
+    let removed_player = old_game.players.iter().find(|name| {
+        let still_playing = old_game.player_id(name).is_some_and(|id| game.player_ids.contains(id));
+        !still_playing && hands.iter().any(|h| h.players.contains(name))
+    });
+    if let Some(name) = removed_player {
+        return Some(format!("{} has played hands in this game, so can't be removed from it: delete or edit those hands first", name));
+    }
+    let removed_table = old_game.tables.iter().find(|table| {
+        !game.tables.contains(table)
+            && !renamed_tables.iter().any(|(old, _)| old == *table)
+            && hands.iter().any(|h| h.table == **table)
+    });
+    removed_table.map(|table| format!("Table {} has hands in this game, so can't be removed from it: delete or edit those hands first", table))
+}
+
+/// The page for when saving `mine` found that someone else had just saved a hand in its place, or
+/// (when moving it) at `old_hand_id`.
+async fn hand_conflict(client: &aws_sdk_dynamodb::Client, game: Game, mine: CompletedHand, old_hand_id: Option<&str>) -> Result<Response, DbError> {
+    let hands = get_hands(client, &game).await?;
+    let theirs = hands.iter()
+        .find(|h| h.hand_id() == mine.hand_id())
+        .or_else(|| hands.iter().find(|h| Some(h.hand_id().as_str()) == old_hand_id))
+        .cloned();
+    Ok(Response::HandConflict { game, mine, theirs, old_hand_id: old_hand_id.map(|id| id.to_string()) })
+}
+
+/// The page asking for confirmation, unless it's been given, when `hand` looks like one of the
+/// game's other hands entered again.  `old_hand_id` is where `hand` was, if it's being edited.
+async fn suspected_duplicate(
+    client: &aws_sdk_dynamodb::Client,
+    game: &Game,
+    hand: &CompletedHand,
+    old_hand_id: Option<&str>,
+    action: Route,
+    form_data: &[(String, String)],
+) -> Result<Option<Response>, DbError> {
+    if form_data.iter().any(|(k, _)| k == "confirmDuplicate") {
+        return Ok(None);
+    }
+    let hands = get_hands(client, game).await?;
+    let duplicate = hands.into_iter().find(|h| {
+        h.hand_id() != hand.hand_id() && Some(h.hand_id().as_str()) != old_hand_id && is_likely_duplicate(h, hand)
+    });
+    Ok(duplicate.map(|duplicate| Response::SuspectedDuplicate {
+        game: game.clone(),
+        mine: hand.clone(),
+        duplicate,
+        action,
+        passthrough: form_data.iter().filter(|(k, _)| k == "submissionToken" || k == "returnTo" || k == "author").cloned().collect(),
+    }))
+}
+
+/// `hand`, as loaded for `old_game`, with its players under the names they have in `game`, which
+/// may have renamed some of them.
+fn with_new_names(old_game: &Game, game: &Game, hand: CompletedHand) -> CompletedHand {
+    let rename = |name: String| old_game.player_id(&name)
+        .and_then(|id| game.player_name(id))
+        .cloned()
+        .unwrap_or(name);
+    CompletedHand {
+        players: hand.players.into_iter().map(rename).collect(),
+        bidder: rename(hand.bidder),
+        partner: hand.partner.map(rename),
+        defence: hand.defence.into_iter().map(rename).collect(),
+        ..hand
+    }
+}
+
+/// Where a response sends the browser, if it's a redirect.
+fn redirect_location(response: &Response) -> Option<String> {
+    match response {
+        Response::Redirect { location } => Some(location.clone()),
+        Response::RedirectToGame { game } => Some(url_for(&Route::Game { game_id: game.game_id.clone() })),
+        _ => None,
+    }
+}
+
+/// Forms that save something carry a one-time `submissionToken`.  A form submitted again with
+/// the same token (a double tap, or the browser resending it) is sent where the first submission
+/// went, rather than saved again.  The token is reserved before saving, so that a second
+/// submission arriving while the first is being saved is turned away too.
+#[allow(clippy::too_many_arguments)]
 pub async fn handle(
     client: &aws_sdk_dynamodb::Client,
     host: &str,
     method: &Method,
     path: &str,
+    query_params: &[(String, String)],
+    form_data: &Option<Vec<(String, String)>>,
+    user_agent: Option<&str>,
+    device: &str,
+) -> Result<Response, LambdaError> {
+    let token = form_data.as_ref()
+        .and_then(|f| f.iter().find(|(k, _)| k == "submissionToken"))
+        .map(|(_, v)| v.clone())
+        .filter(|t| !t.is_empty());
+
+    if let Some(ref token) = token {
+        match reserve_submission(client, token).await? {
+            None => {},
+            Some(Submission::Redirected(location)) => return Ok(Response::Redirect { location }),
+            Some(Submission::Pending) => return Ok(Response::ValidationError {
+                msg: "This form is already being saved: wait a moment, then reload the page".to_string(),
+            }),
+        }
+    }
+
+    let name = form_data.as_ref()
+        .and_then(|f| f.iter().find(|(k, _)| k == "author"))
+        .map(|(_, v)| v.as_str());
+    let author = author_label(name, user_agent);
+
+    let result = match dispatch(client, host, method, path, query_params, form_data, &author, device).await {
+        Ok(response) => Ok(response),
+        // Finished (or deleted) since it was loaded.
+        Err(e) => match e.downcast::<DbError>().map(|e| *e) {
+            Ok(DbError::Locked(game_id)) => match get_game(client, &game_id).await {
+                Ok(Some(game)) => Ok(Response::GameLocked { game }),
+                Ok(None) => Ok(Response::GameNotFound { game_id }),
+                Err(e) => Err(e.into()),
+            },
+            Ok(e) => Err(e.into()),
+            Err(e) => Err(e),
+        },
+    };
+
+    // The submission is saved by now, so it's sent where it went even if that can't be recorded.
+    // Anything else saved nothing, and can be submitted again.
+    if let Some(token) = token {
+        match result.as_ref().ok().and_then(redirect_location) {
+            Some(location) => if let Err(e) = put_submission(client, &token, &location).await {
+                tracing::error!("Can't record submission {:?}: {}", token, e);
+            },
+            None => if let Err(e) = delete_submission(client, &token).await {
+                tracing::error!("Can't release submission {:?}: {}", token, e);
+            },
+        }
+    }
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn dispatch(
+    client: &aws_sdk_dynamodb::Client,
+    host: &str,
+    method: &Method,
+    path: &str,
+    query_params: &[(String, String)],
     form_data: &Option<Vec<(String, String)>>,
+    author: &str,
+    device: &str,
 ) -> Result<Response, LambdaError> {
     let response = if let Some(route) = match_route(path) {
         match (method, route, form_data) {
             // GET /
-            (&Method::GET, Route::Index, _) => Response::CreateGamePage,
+            (&Method::GET, Route::Index, _) => {
+                let (search, cursor) = match (http_utils::query_to_game_search(query_params), http_utils::query_to_cursor(query_params)) {
+                    (Ok(search), Ok(cursor)) => (search, cursor),
+                    (Err(e), _) | (_, Err(e)) => return Ok(Response::ValidationError { msg: e.to_string() }),
+                };
+                let registry = get_players_cached(client).await?;
+                let players: Vec<Player> = registry.iter()
+                    .filter(|p| !search.player.is_empty() && p.matches(&search.player))
+                    .cloned()
+                    .collect();
+                if players.len() > MAX_SEARCH_PLAYERS {
+                    return Ok(Response::ValidationError {
+                        msg: format!("{} players match {}: type more of the name", players.len(), search.player),
+                    });
+                }
+                let (games, next_cursor) = list_games(client, &search, &players, cursor.as_ref(), GAMES_PER_PAGE).await?;
+                Response::IndexPage { games, search, next_cursor, registry }
+            },
             
             // POST /games
             (&Method::POST, Route::Games, Some(form_data)) => {
-                match http_utils::form_data_to_game(Uuid::new_v4().to_string(), form_data) {
-                    Ok(game) => {
-                        put_game(client, &game).await?;
+                let registry = form_registry(client, form_data).await?;
+                match http_utils::form_data_to_game(Uuid::new_v4().to_string(), form_data, &registry) {
+                    Ok((game, changed_players)) => {
+                        let mut transaction = Transaction::new(author)?;
+                        transaction.put_game(None, &game, &[])?;
+                        for player in &changed_players {
+                            transaction.put_player(player);
+                        }
+                        transaction.send(client).await?;
                         Response::RedirectToGame { game }
                     },
                     Err(e) => Response::ValidationError { msg: e.to_string() },
@@ -38,10 +255,17 @@ pub async fn handle(
             // GET /games/{game_id}
             (&Method::GET, Route::Game { game_id }, _) => {
                 if let Some(game) = get_game(client, &game_id).await? {
-                    let hands = get_hands(client, &game_id).await?;
-                    match scoring::score_hands(hands.clone()) {
-                        Ok((hands_with_scores, total_scores, player_hand_count)) => {
-                            Response::GamePage { game, hands_with_scores, total_scores, player_hand_count }
+                    let (hands, trash) = get_hands_and_trash(client, &game).await?;
+                    let deleted = query_params.iter()
+                        .find(|(k, _)| k == "deleted")
+                        .and_then(|(_, trash_id)| trash.iter().find(|h| h.trash_id().as_ref() == Some(trash_id)))
+                        .cloned();
+                    let timing = stats::session_timing(&game.tables, &hands);
+                    let (counted, uncounted) = hands.into_iter().partition(|h| game.counts(h));
+                    match scoring::summarise(&game.ranking_method, counted) {
+                        Ok(summary) => {
+                            let stats = stats::player_stats(&game.players, &summary.hands_with_scores);
+                            Response::GamePage { game, summary, stats, timing, uncounted, trash, deleted }
                         },
                         Err(err) => {
                             Response::ValidationError { msg: format!("Error scoring hands: {:?}", err) }
@@ -52,22 +276,315 @@ pub async fn handle(
                 }
             }
             
+            // GET /games/{game_id}/edit
+            (&Method::GET, Route::GameEdit { game_id }, _) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    Response::EditGamePage { game, registry: get_players_cached(client).await? }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
+            // GET /games/{game_id}/clone
+            (&Method::GET, Route::GameClone { game_id }, _) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    let template = Game {
+                        // Games are played in Paris, where the day starts an hour or two before it does in UTC.
+                        date: paris_today(),
+                        status: GameStatus::Planned,
+                        final_standings: vec![],
+                        archived: false,
+                        version: 0,
+                        ..game
+                    };
+                    Response::NewGameLikePage { template, registry: get_players_cached(client).await? }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
+            // GET /games/{game_id}/admin
+            (&Method::GET, Route::GameAdmin { game_id }, _) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    let hands = get_hands(client, &game).await?;
+                    let same_day = GameSearch { from: game.date.clone(), to: game.date.clone(), ..GameSearch::default() };
+                    let (same_day_games, _) = list_games(client, &same_day, &[], None, GAMES_PER_PAGE).await?;
+                    let same_day_games = same_day_games.into_iter().map(|l| l.game).filter(|g| g.game_id != game.game_id).collect();
+                    Response::GameAdminPage { game, hands, same_day_games }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
+            // POST /games/{game_id}/merge
+            (&Method::POST, Route::GameMerge { game_id }, Some(form_data)) => {
+                let target_game_id = form_data.iter().find(|(k, _)| k == "targetGameId").map(|(_, v)| v.clone()).unwrap_or_default();
+                match (get_game(client, &game_id).await?, get_game(client, &target_game_id).await?) {
+                    (Some(source), _) if source.is_finished() => Response::GameLocked { game: source },
+                    (Some(source), _) if source.game_id == target_game_id => {
+                        Response::ValidationError { msg: "A game can't be merged into itself".to_string() }
+                    },
+                    (Some(_), Some(target)) if target.is_finished() => Response::GameLocked { game: target },
+                    (Some(source), Some(target)) if source.date != target.date => Response::ValidationError {
+                        msg: format!("Only games on the same day can be merged, and this one is on {}", target.date),
+                    },
+                    (Some(source), Some(target)) => {
+                        let source_hands = get_hands(client, &source).await?;
+                        let target_hands = get_hands(client, &target).await?;
+                        let (merged, moves) = match rearrange::merge_games(&source, &source_hands, &target, &target_hands) {
+                            Ok(merge) => merge,
+                            Err(msg) => return Ok(Response::ValidationError { msg }),
+                        };
+                        // Checked as when saving a game: see `form_data_to_game`.
+                        if let Some((a, b)) = find_near_duplicate(&merged.players) {
+                            return Ok(Response::ValidationError {
+                                msg: format!("{} and {} are too similar: rename one of them before merging these games", a, b),
+                            });
+                        }
+                        // The source game is left empty, but its links may still be around.
+                        let archived = Game { archived: true, ..source.clone() };
+                        let mut transaction = Transaction::new(author)?;
+                        transaction.put_game(Some(&target), &merged, &target_hands)?;
+                        transaction.put_game(Some(&source), &archived, &source_hands)?;
+                        transaction.move_hands(&[&merged, &archived], &moves, &source_hands)?;
+                        match transaction.send(client).await {
+                            Ok(()) => Response::RedirectToGame { game: merged },
+                            Err(DbError::TooManyWrites(_)) => Response::ValidationError {
+                                msg: "These games have too many hands to merge at once".to_string(),
+                            },
+                            Err(e) => return Err(e.into()),
+                        }
+                    },
+                    (Some(_), None) => Response::GameNotFound { game_id: target_game_id },
+                    (None, _) => Response::GameNotFound { game_id },
+                }
+            }
+
+            // POST /games/{game_id}/split
+            (&Method::POST, Route::GameSplit { game_id }, Some(form_data)) => {
+                let table = form_data.iter().find(|(k, _)| k == "table").map(|(_, v)| v.clone()).unwrap_or_default();
+                match get_game(client, &game_id).await? {
+                    Some(game) if game.is_finished() => Response::GameLocked { game },
+                    Some(game) if game.tables.len() < 2 && game.tables.contains(&table) => {
+                        Response::ValidationError { msg: format!("{} is the game's only table", table) }
+                    },
+                    Some(game) if game.tables.contains(&table) => {
+                        let hands = get_hands(client, &game).await?;
+                        let (remaining, new_game, moves) = match rearrange::split_table(&game, &hands, &table, Uuid::new_v4().to_string()) {
+                            Ok(split) => split,
+                            Err(msg) => return Ok(Response::ValidationError { msg }),
+                        };
+                        let mut transaction = Transaction::new(author)?;
+                        transaction.put_game(Some(&game), &remaining, &hands)?;
+                        transaction.put_game(None, &new_game, &[])?;
+                        transaction.move_hands(&[&remaining, &new_game], &moves, &hands)?;
+                        match transaction.send(client).await {
+                            Ok(()) => Response::RedirectToGame { game: new_game },
+                            Err(DbError::TooManyWrites(_)) => Response::ValidationError {
+                                msg: format!("Table {} has too many hands to move to a new game at once", table),
+                            },
+                            Err(e) => return Err(e.into()),
+                        }
+                    },
+                    Some(_) => Response::TableNotFound { game_id, table },
+                    None => Response::GameNotFound { game_id },
+                }
+            }
+
+            // POST /games/{game_id}/renumber
+            (&Method::POST, Route::GameRenumber { game_id }, Some(form_data)) => {
+                let table = form_data.iter().find(|(k, _)| k == "table").map(|(_, v)| v.clone()).unwrap_or_default();
+                match get_game(client, &game_id).await? {
+                    Some(game) if game.is_finished() => Response::GameLocked { game },
+                    Some(game) if game.tables.contains(&table) => {
+                        let hands = get_hands(client, &game).await?;
+                        let moves = rearrange::renumber_table(&game, &hands, &table);
+                        let mut transaction = Transaction::new(author)?;
+                        transaction.move_hands(&[&game], &moves, &hands)?;
+                        match transaction.send(client).await {
+                            Ok(()) => Response::Redirect { location: url_for(&Route::GameAdmin { game_id }) },
+                            Err(DbError::TooManyWrites(_)) => Response::ValidationError {
+                                msg: format!("Too many hands to renumber at table {} at once", table),
+                            },
+                            Err(e) => return Err(e.into()),
+                        }
+                    },
+                    Some(_) => Response::TableNotFound { game_id, table },
+                    None => Response::GameNotFound { game_id },
+                }
+            }
+
+            // POST /games/{game_id}/finish
+            (&Method::POST, Route::GameFinish { game_id }, _) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    let hands = get_hands(client, &game).await?;
+                    if let Some(msg) = game.finish_blocker(&hands) {
+                        return Ok(Response::ValidationError { msg });
+                    }
+                    let counted = hands.iter().filter(|h| game.counts(h)).cloned().collect();
+                    match scoring::summarise(&game.ranking_method, counted) {
+                        Ok(summary) => {
+                            let finished = Game { status: GameStatus::Finished, final_standings: summary.ranking, ..game.clone() };
+                            let mut transaction = Transaction::new(author)?;
+                            transaction.put_game(Some(&game), &finished, &hands)?;
+                            match transaction.send(client).await {
+                                Ok(()) => Response::RedirectToGame { game: finished },
+                                Err(DbError::Conflict(_)) => Response::ValidationError {
+                                    msg: "Someone changed this game just now: check it again before finishing it".to_string(),
+                                },
+                                Err(e) => return Err(e.into()),
+                            }
+                        },
+                        Err(err) => Response::ValidationError { msg: format!("Error scoring hands: {:?}", err) },
+                    }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
+            // POST /games/{game_id}/reopen
+            (&Method::POST, Route::GameReopen { game_id }, _) => {
+                match get_game(client, &game_id).await? {
+                    Some(game) if game.is_finished() => {
+                        let reopened = Game { status: GameStatus::InProgress, final_standings: vec![], ..game.clone() };
+                        let hands = get_hands(client, &game).await?;
+                        let mut transaction = Transaction::new(author)?;
+                        transaction.put_game(Some(&game), &reopened, &hands)?;
+                        match transaction.send(client).await {
+                            Ok(()) => Response::RedirectToGame { game: reopened },
+                            Err(DbError::Conflict(_)) => Response::ValidationError {
+                                msg: "Someone changed this game just now: check it again before reopening it".to_string(),
+                            },
+                            Err(e) => return Err(e.into()),
+                        }
+                    },
+                    Some(game) => Response::RedirectToGame { game },
+                    None => Response::GameNotFound { game_id },
+                }
+            }
+
+            // GET /games/{game_id}/history
+            (&Method::GET, Route::GameHistory { game_id }, _) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    let entries = get_history(client, &game_id).await?;
+                    Response::GameHistoryPage { game, entries }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
             // GET /games/{game_id}/qrcode
             (&Method::GET, Route::GameQRCode { game_id }, _) => {
                 if let Some(game) = get_game(client, &game_id).await? {
-                    Response::QRCode { domain_name: host.to_string(), game_id: game.game_id }
+                    Response::QRCode { domain_name: host.to_string(), route: Route::Game { game_id: game.game_id } }
                 } else {
                     Response::GameNotFound { game_id }
                 }
             }
+
+            // GET /games/{game_id}/tables/{table}
+            (&Method::GET, Route::GameTable { game_id, table }, _) => {
+                match get_game(client, &game_id).await? {
+                    Some(game) if game.tables.contains(&table) => {
+                        let (counted, uncounted) = get_hands(client, &game).await?
+                            .into_iter()
+                            .filter(|h| h.table == table)
+                            .partition(|h| game.counts(h));
+                        match scoring::summarise(&game.ranking_method, counted) {
+                            Ok(summary) => Response::GameTablePage { game, table, summary, uncounted },
+                            Err(err) => {
+                                Response::ValidationError { msg: format!("Error scoring hands: {:?}", err) }
+                            }
+                        }
+                    },
+                    Some(_) => Response::TableNotFound { game_id, table },
+                    None => Response::GameNotFound { game_id },
+                }
+            }
+
+            // GET /games/{game_id}/tables/{table}/qrcode
+            (&Method::GET, Route::GameTableQRCode { game_id, table }, _) => {
+                match get_game(client, &game_id).await? {
+                    Some(game) if game.tables.contains(&table) => {
+                        Response::QRCode { domain_name: host.to_string(), route: Route::GameTable { game_id, table } }
+                    },
+                    Some(_) => Response::TableNotFound { game_id, table },
+                    None => Response::GameNotFound { game_id },
+                }
+            }
             
+            // quasi DELETE /games/{game_id}
+            (&Method::POST, Route::Game { game_id }, Some(form_data)) if is_delete(form_data) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    match delete_game(client, &game).await {
+                        Ok(()) => Response::Redirect { location: url_for(&Route::Index) },
+                        Err(DbError::Conflict(_)) => Response::ValidationError {
+                            msg: "Someone changed this game just now: check it again before deleting it".to_string(),
+                        },
+                        Err(e) => return Err(e.into()),
+                    }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
             // POST /games/{game_id}
             (&Method::POST, Route::Game { game_id }, Some(form_data)) => {
-                if let Some(game) = get_game(client, &game_id).await? {
-                    match http_utils::form_data_to_game(game.game_id, form_data) {
-                        Ok(game) => {
-                            put_game(client, &game).await?;
-                            Response::RedirectToGame { game }
+                if let Some(old_game) = get_game(client, &game_id).await? {
+                    if old_game.is_finished() {
+                        return Ok(Response::GameLocked { game: old_game });
+                    }
+                    let registry = form_registry(client, form_data).await?;
+                    match http_utils::form_data_to_game(old_game.game_id.clone(), form_data, &registry) {
+                        Ok((game, changed_players)) => {
+                            let game = Game { status: old_game.status.clone(), ..game };
+                            let renamed_tables = renamed_names(&old_game.tables, &game.tables);
+                            let hands = get_hands(client, &old_game).await?;
+                            if let Some(msg) = check_removals(&old_game, &game, &hands, &renamed_tables) {
+                                return Ok(Response::ValidationError { msg });
+                            }
+
+                            // Hands refer to their table by name, so renamed tables' hands move with them.
+                            let new_hands: Vec<CompletedHand> = hands.into_iter().map(|h| with_new_names(&old_game, &game, h)).collect();
+                            let moves: Vec<HandMove> = if renamed_tables.is_empty() {
+                                vec![]
+                            } else {
+                                new_hands.iter()
+                                    .cloned()
+                                    .filter_map(|hand| {
+                                        let (_, new_table) = renamed_tables.iter().find(|(old, _)| *old == hand.table)?;
+                                        Some(HandMove {
+                                            from_game_id: game.game_id.clone(),
+                                            from_hand_id: hand.hand_id(),
+                                            to_game_id: game.game_id.clone(),
+                                            hand: CompletedHand { table: new_table.clone(), ..hand },
+                                        })
+                                    })
+                                    .collect()
+                            };
+                            let mut transaction = Transaction::new(author)?;
+                            transaction.put_game(Some(&old_game), &game, &new_hands)?;
+                            for player in &changed_players {
+                                transaction.put_player(player);
+                            }
+                            transaction.move_hands(&[&game], &moves, &new_hands)?;
+                            match transaction.send(client).await {
+                                Ok(()) => Response::RedirectToGame { game },
+                                Err(DbError::Conflict(_)) => match get_game(client, &game_id).await? {
+                                    Some(theirs) => Response::GameConflict { mine: game, theirs },
+                                    None => Response::GameNotFound { game_id },
+                                },
+                                Err(DbError::TooManyWrites(_)) => Response::ValidationError {
+                                    msg: "The renamed tables have too many hands to rename at once".to_string(),
+                                },
+                                Err(e) => return Err(e.into()),
+                            }
                         },
                         Err(e) => Response::ValidationError { msg: e.to_string() }
                     }
@@ -79,13 +596,36 @@ pub async fn handle(
             // POST /games/{game_id}/hands
             (&Method::POST, Route::GameHands { game_id }, Some(form_data)) => {
                 if let Some(game) = get_game(client, &game_id).await? {
-                    match http_utils::form_data_to_hand(form_data) {
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    match http_utils::form_data_to_hand(form_data, &game) {
                         Ok(hand) => {
                             // Make sure the hand is valid before saving it
                             match scoring::score(&hand) {   
                                 Ok(_scores) => {
-                                    put_hand(&client, &game_id, &hand).await?;
-                                    Response::RedirectToGame { game }
+                                    let now = DateTime::from(SystemTime::now()).fmt(Format::DateTime)?;
+                                    let hand = CompletedHand {
+                                        created_at: Some(now.clone()),
+                                        updated_at: Some(now),
+                                        entered_by: Some(author.to_string()),
+                                        entered_device: Some(device.to_string()),
+                                        ..hand
+                                    };
+                                    let action = Route::GameHands { game_id: game_id.clone() };
+                                    if let Some(response) = suspected_duplicate(client, &game, &hand, None, action, form_data).await? {
+                                        return Ok(response);
+                                    }
+                                    let mut transaction = Transaction::new(author)?;
+                                    transaction.add_hand(&game, &hand)?;
+                                    if game.status == GameStatus::Planned {
+                                        transaction.start_game(&game);
+                                    }
+                                    match transaction.send(client).await {
+                                        Ok(()) => redirect_after_save(game, form_data),
+                                        Err(DbError::Conflict(_)) => hand_conflict(client, game, hand, None).await?,
+                                        Err(e) => return Err(e.into()),
+                                    }
                                 },
                                 Err(e) => Response::ValidationError { msg: e.to_string() },
                             }
@@ -100,10 +640,10 @@ pub async fn handle(
             // GET /games/{game_id}/hands/{hand_id}
             (&Method::GET, Route::GameHand { game_id, hand_id }, _) => {
                 if let Some(game) = get_game(client, &game_id).await? {
-                    let hands = get_hands(client, &game_id).await?;
+                    let hands = get_hands(client, &game).await?;
                     let hand = hands.iter().find(|h| h.hand_id() == hand_id).cloned();
                     if let Some(hand) = hand {
-                        Response::EditHandPage { game, hands: hands, hand }
+                        Response::EditHandPage { game, hands, hand }
                     } else {
                         Response::HandNotFound { game_id, hand_id }
                     }
@@ -115,8 +655,98 @@ pub async fn handle(
             // quasi DELETE /games/{game_id}/hands/{hand_id}
             (&Method::POST, Route::GameHand { game_id, hand_id }, Some(form_data)) if is_delete(form_data) => {
                 if let Some(game) = get_game(client, &game_id).await? {
-                    delete_hand(client, &game_id, &hand_id).await?;
-                    Response::RedirectToGame { game }
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    // Deleting a hand that's already gone does nothing, but one that's been changed
+                    // since it was read is shown as it is now rather than deleted.
+                    let mut deleted = None;
+                    if let Some(hand) = get_hands(client, &game).await?.into_iter().find(|h| h.hand_id() == hand_id) {
+                        let mut transaction = Transaction::new(author)?;
+                        let trashed = transaction.delete_hand(&game, &hand)?;
+                        match transaction.send(client).await {
+                            Ok(()) => deleted = trashed.trash_id(),
+                            Err(DbError::Conflict(_)) => return Ok(hand_conflict(client, game, hand, Some(&hand_id)).await?),
+                            Err(e) => return Err(e.into()),
+                        }
+                    }
+                    match deleted {
+                        Some(trash_id) => {
+                            let query = serde_urlencoded::to_string([("deleted", &trash_id)])?;
+                            Response::Redirect { location: format!("{}?{}", url_for(&Route::Game { game_id }), query) }
+                        },
+                        None => Response::RedirectToGame { game },
+                    }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
+            // POST /games/{game_id}/hands/{hand_id}/restore
+            (&Method::POST, Route::GameHandRestore { game_id, hand_id }, _) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    let trash = get_deleted_hands(client, &game).await?;
+                    match trash.iter().find(|h| h.trash_id().as_ref() == Some(&hand_id)) {
+                        Some(hand) if !game.tables.contains(&hand.table) => Response::ValidationError {
+                            msg: format!("Table {} is no longer in this game, so this hand can't be restored to it", hand.table),
+                        },
+                        Some(hand) => {
+                            let mut transaction = Transaction::new(author)?;
+                            transaction.restore_hand(&game, hand)?;
+                            match transaction.send(client).await {
+                                Ok(()) => Response::RedirectToGame { game },
+                                Err(DbError::Conflict(_)) => Response::ValidationError {
+                                    msg: format!("Hand {} is no longer in the trash, or another hand has been saved in its place", hand.hand_id()),
+                                },
+                                Err(e) => return Err(e.into()),
+                            }
+                        },
+                        None => Response::HandNotFound { game_id, hand_id },
+                    }
+                } else {
+                    Response::GameNotFound { game_id }
+                }
+            }
+
+            // POST /games/{game_id}/hands/{hand_id}/confirm
+            (&Method::POST, Route::GameHandConfirm { game_id, hand_id }, Some(form_data)) => {
+                if let Some(game) = get_game(client, &game_id).await? {
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    let hands = get_hands(client, &game).await?;
+                    let confirmed_by = form_data.iter().find(|(k, _)| k == "confirmedBy").map(|(_, v)| v.clone()).unwrap_or_default();
+                    match hands.into_iter().find(|h| h.hand_id() == hand_id) {
+                        Some(hand) if hand.confirmed_by.is_some() => Response::ValidationError {
+                            msg: format!("Hand #{} at table {} is already confirmed", hand.hand_number, hand.table),
+                        },
+                        Some(hand) if !hand.players.contains(&confirmed_by) => Response::ValidationError {
+                            msg: format!("Only a player of hand #{} at table {} can confirm it", hand.hand_number, hand.table),
+                        },
+                        Some(hand) if hand.entered_by.as_deref().is_some_and(|e| same_name(e, &confirmed_by)) => Response::ValidationError {
+                            msg: format!("{} entered this hand, so someone else must confirm it", confirmed_by),
+                        },
+                        // Whoever entered it could otherwise confirm it under another player's name.
+                        Some(hand) if hand.entered_device.as_deref() == Some(device) => Response::ValidationError {
+                            msg: "This hand was entered on this device, so it must be confirmed on another player's".to_string(),
+                        },
+                        Some(hand) => {
+                            let confirmed = CompletedHand { confirmed_by: Some(confirmed_by), ..hand.clone() };
+                            let mut transaction = Transaction::new(author)?;
+                            transaction.update_hand(&game, &hand, &confirmed)?;
+                            match transaction.send(client).await {
+                                Ok(()) => redirect_after_save(game, form_data),
+                                Err(DbError::Conflict(_)) => Response::ValidationError {
+                                    msg: "Someone changed this hand just now: check it again before confirming it".to_string(),
+                                },
+                                Err(e) => return Err(e.into()),
+                            }
+                        },
+                        None => Response::HandNotFound { game_id, hand_id },
+                    }
                 } else {
                     Response::GameNotFound { game_id }
                 }
@@ -125,17 +755,53 @@ pub async fn handle(
             // POST /games/{game_id}/hands/{hand_id}
             (&Method::POST, Route::GameHand { game_id, hand_id }, Some(form_data)) => {
                 if let Some(game) = get_game(client, &game_id).await? {
-                    match http_utils::form_data_to_hand(form_data) {
+                    if game.is_finished() {
+                        return Ok(Response::GameLocked { game });
+                    }
+                    match http_utils::form_data_to_hand(form_data, &game) {
                         Ok(hand) => {
                             // Make sure the hand is valid before saving it
                             match scoring::score(&hand) {   
                                 Ok(_scores) => {
-                                    // if hand_id is being changed, and if so, delete the old hand
-                                    if hand.hand_id() != hand_id {
-                                        delete_hand(client, &game_id, &hand_id).await?;
+                                    let hands = get_hands(client, &game).await?;
+                                    let before = hands.iter().find(|h| h.hand_id() == hand_id).cloned();
+                                    let now = DateTime::from(SystemTime::now()).fmt(Format::DateTime)?;
+                                    let hand = CompletedHand {
+                                        created_at: before.as_ref().and_then(|h| h.created_at.clone()),
+                                        updated_at: Some(now),
+                                        // A changed hand needs confirming again.
+                                        entered_by: Some(author.to_string()),
+                                        confirmed_by: None,
+                                        entered_device: Some(device.to_string()),
+                                        ..hand
+                                    };
+                                    let action = Route::GameHand { game_id: game_id.clone(), hand_id: hand_id.clone() };
+                                    if let Some(response) = suspected_duplicate(client, &game, &hand, Some(&hand_id), action, form_data).await? {
+                                        return Ok(response);
+                                    }
+                                    let saved = match &before {
+                                        // Someone else has deleted (or moved) it since.
+                                        None => Err(DbError::Conflict(format!("Hand {} is gone", hand_id))),
+                                        Some(before) => {
+                                            let mut transaction = Transaction::new(author)?;
+                                            if hand.hand_id() != hand_id {
+                                                // The conflict page offers to replace a hand saved at the new ID since.
+                                                let replacing = form_data.iter()
+                                                    .find(|(k, _)| k == "replaceVersion")
+                                                    .and_then(|(_, v)| v.parse::<i32>().ok())
+                                                    .and_then(|version| hands.iter().find(|h| h.hand_id() == hand.hand_id() && h.version == version));
+                                                transaction.move_hand(&game, before, &hand, replacing)?;
+                                            } else {
+                                                transaction.update_hand(&game, before, &hand)?;
+                                            }
+                                            transaction.send(client).await
+                                        },
+                                    };
+                                    match saved {
+                                        Ok(()) => Response::RedirectToGame { game },
+                                        Err(DbError::Conflict(_)) => hand_conflict(client, game, hand, Some(&hand_id)).await?,
+                                        Err(e) => return Err(e.into()),
                                     }
-                                    put_hand(&client, &game_id, &hand).await?;
-                                    Response::RedirectToGame { game }
                                 },
                                 Err(e) => Response::ValidationError { msg: e.to_string() },
                             }
@@ -147,6 +813,25 @@ pub async fn handle(
                 }
             }
 
+            // GET /players
+            (&Method::GET, Route::Players, _) => Response::PlayersPage { players: get_players(client).await? },
+
+            // POST /players/{player_id}
+            (&Method::POST, Route::Player { player_id }, Some(form_data)) => {
+                if let Some(player) = get_player(client, &player_id).await? {
+                    let registry = get_players(client).await?;
+                    match http_utils::form_data_to_player(player, form_data, &registry) {
+                        Ok(player) => {
+                            put_player(client, &player).await?;
+                            Response::Redirect { location: url_for(&Route::Players) }
+                        },
+                        Err(e) => Response::ValidationError { msg: e.to_string() },
+                    }
+                } else {
+                    Response::NotFound
+                }
+            }
+
             // 405
             _ => Response::HttpMethodNotAllowed { method: method.clone(), path: path.to_string() },
         }
@@ -155,3 +840,60 @@ pub async fn handle(
     };
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Confirmation, RankingMethod};
+
+    fn game(players: &[&str], tables: &[&str]) -> Game {
+        Game {
+            game_id: "game".to_string(),
+            date: "2025-03-14".to_string(),
+            host: "Marie".to_string(),
+            players: players.iter().map(|p| p.to_string()).collect(),
+            player_ids: players.iter().map(|p| format!("id-{}", p)).collect(),
+            tables: tables.iter().map(|t| t.to_string()).collect(),
+            ranking_method: RankingMethod::Total,
+            confirmation: Confirmation::Off,
+            status: GameStatus::InProgress,
+            final_standings: vec![],
+            archived: false,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_removals() {
+        let old_game = game(&["Alice", "Bob", "Charlie", "David"], &["Atout", "Excuse"]);
+        let hands = vec![CompletedHand::test(&["Alice", "Bob", "Charlie"], "Alice", None)];
+
+        // David and Excuse have no hands, so can go.
+        assert_eq!(check_removals(&old_game, &game(&["Alice", "Bob", "Charlie"], &["Atout"]), &hands, &[]), None);
+
+        let without_bob = game(&["Alice", "Charlie", "David"], &["Atout", "Excuse"]);
+        assert!(check_removals(&old_game, &without_bob, &hands, &[]).is_some_and(|e| e.starts_with("Bob ")));
+
+        // A renamed player keeps their ID, so isn't removed.
+        let mut renamed_bob = game(&["Alice", "Robert", "Charlie", "David"], &["Atout", "Excuse"]);
+        renamed_bob.player_ids[1] = "id-Bob".to_string();
+        assert_eq!(check_removals(&old_game, &renamed_bob, &hands, &[]), None);
+
+        let without_atout = game(&["Alice", "Bob", "Charlie", "David"], &["Excuse"]);
+        assert!(check_removals(&old_game, &without_atout, &hands, &[]).is_some_and(|e| e.starts_with("Table Atout ")));
+        let renamed_atout = game(&["Alice", "Bob", "Charlie", "David"], &["Atouts", "Excuse"]);
+        assert_eq!(check_removals(&old_game, &renamed_atout, &hands, &[("Atout".to_string(), "Atouts".to_string())]), None);
+    }
+
+    #[test]
+    fn test_with_new_names() {
+        let old_game = game(&["Alice", "Bob", "Charlie"], &["Atout"]);
+        let mut game = game(&["Alice", "Robert", "Charlie"], &["Atout"]);
+        game.player_ids[1] = "id-Bob".to_string();
+
+        let hand = with_new_names(&old_game, &game, CompletedHand::test(&["Alice", "Bob", "Charlie"], "Bob", None));
+        assert_eq!(hand.players, vec!["Alice", "Robert", "Charlie"]);
+        assert_eq!(hand.bidder, "Robert");
+        assert_eq!(hand.defence, vec!["Alice", "Charlie"]);
+    }
+}
@@ -1,27 +1,35 @@
-use crate::game::{hand_number_and_table, Bid, Chelem, CompletedHand, Game, Poignée, ValidationError};
+use crate::game::{hand_number_and_table, resolve_players, Bid, Chelem, CompletedHand, Confirmation, Game, GameSearch, GameStatus, Player, Poignée, RankingMethod, ValidationError};
+use crate::names::{find_near_duplicate, normalise_name, same_name};
 
 fn lines(s: &str) -> Vec<String> {
     s.split('\n')
-        .map(|s| s.trim().to_string())
+        .map(normalise_name)
         .filter(|s| !s.is_empty())
         .collect()
 }
 
-fn form_value<'a>(form_data: &'a Vec<(String, String)>, key: &'a str) -> Option<&'a String> {
+/// The one of `names` that `name` is a variant of.
+fn canonical_name<'a>(names: &'a [String], name: &str, what: &str) -> Result<&'a String, ValidationError> {
+    names.iter()
+        .find(|n| same_name(n, name))
+        .ok_or(ValidationError { msg: format!("{} is not a {} in this game", name, what) })
+}
+
+fn form_value<'a>(form_data: &'a [(String, String)], key: &'a str) -> Option<&'a String> {
     form_data
         .iter()
         .find(|(k, _)| k == key)
         .map(|(_, v)| v)
 }
 
-fn bool_form_value(form_data: &Vec<(String, String)>, key: &str) -> bool {
+fn bool_form_value(form_data: &[(String, String)], key: &str) -> bool {
     form_value(form_data, key).map(|s| {
         let s = s.to_lowercase();
         s == "on" || s == "true"
     }).unwrap_or(false)
 }
 
-fn form_values<'a>(form_data: &'a Vec<(String, String)>, key: &str) -> Vec<&'a String> {
+fn form_values<'a>(form_data: &'a [(String, String)], key: &str) -> Vec<&'a String> {
     form_data
         .iter()
         .filter(|(k, _)| k.starts_with(key))
@@ -29,39 +37,134 @@ fn form_values<'a>(form_data: &'a Vec<(String, String)>, key: &str) -> Vec<&'a S
         .collect()
 }
 
-fn reqd_form_value<'a>(form_data: &'a Vec<(String, String)>, key: &'a str) -> Result<&'a String, ValidationError> {
+/// The version of the game or hand that the form was filled in from (0 if it's new).
+fn version_form_value(form_data: &[(String, String)]) -> Result<i32, ValidationError> {
+    match form_value(form_data, "version") {
+        Some(s) if !s.is_empty() => s.parse().map_err(|_| ValidationError { msg: format!("Invalid version: {}", s) }),
+        _ => Ok(0),
+    }
+}
+
+fn reqd_form_value<'a>(form_data: &'a [(String, String)], key: &'a str) -> Result<&'a String, ValidationError> {
     form_value(form_data, key).ok_or(ValidationError { msg: format!("Missing required field: {}", key) })
 }
 
-pub fn form_data_to_game(game_id: String, form_data: &Vec<(String, String)>) -> Result<Game, ValidationError> {
-   Ok(Game {
+/// Builds a game from its form, in which players are picked from the registry (`playerIds`) or
+/// typed in (`newPlayers`).  New names are matched against the registry's display names and
+/// aliases, and any that aren't found are returned as new players for the registry.
+///
+/// Players can also be renamed (`playerName.{player_id}`), which renames them in the registry:
+/// they're returned alongside the new players, keeping their old name as an alias.  Hands refer to
+/// players by ID, so they follow without being rewritten.
+pub fn form_data_to_game(game_id: String, form_data: &[(String, String)], registry: &[Player]) -> Result<(Game, Vec<Player>), ValidationError> {
+    let mut players: Vec<Player> = form_values(form_data, "playerIds")
+        .iter()
+        .map(|id| registry.iter()
+            .find(|p| p.player_id == **id)
+            .cloned()
+            .ok_or(ValidationError { msg: format!("Unknown player: {}", id) }))
+        .collect::<Result<_, _>>()?;
+
+    let mut changed_players = vec![];
+    for player in players.iter_mut() {
+        let key = format!("playerName.{}", player.player_id);
+        let name = match form_value(form_data, &key) {
+            Some(name) => normalise_name(name),
+            None => continue,
+        };
+        if name.is_empty() || name == player.display_name {
+            continue;
+        }
+        if let Some(other) = registry.iter().find(|p| p.player_id != player.player_id && p.is_called(&name)) {
+            return Err(ValidationError { msg: format!("{} is already used by {}", name, other.display_name) });
+        }
+        // Only keep the old name if it would no longer be recognised.
+        let old_name = std::mem::replace(&mut player.display_name, name);
+        if !player.is_called(&old_name) {
+            player.aliases.push(old_name);
+        }
+        changed_players.push(player.clone());
+    }
+
+    let new_names = form_value(form_data, "newPlayers").map(|s| lines(s)).unwrap_or_default();
+    let (named_players, new_players) = resolve_players(&new_names, registry);
+    for player in named_players {
+        if !players.iter().any(|p| p.player_id == player.player_id) {
+            players.push(player);
+        }
+    }
+    changed_players.extend(new_players);
+
+    // Hands and scores refer to players by display name, so they mustn't be confusable within a game.
+    let display_names: Vec<String> = players.iter().map(|p| p.display_name.clone()).collect();
+    if let Some((a, b)) = find_near_duplicate(&display_names) {
+        return Err(ValidationError { msg: format!("{} and {} are too similar: pick one, or make one an alias of the other", a, b) });
+    }
+
+    let tables = if let Some(tables) = form_value(form_data, "tables") {
+        lines(tables)
+    } else {
+        vec![]
+    };
+    if let Some((a, b)) = find_near_duplicate(&tables) {
+        return Err(ValidationError { msg: format!("Tables {} and {} are too similar", a, b) });
+    }
+
+    let game = Game {
         game_id,
         date: reqd_form_value(form_data, "date")?.clone(),
-        host: reqd_form_value(form_data, "host")?.clone(),
-        players: if let Some(players) = form_value(form_data, "players") {
-            lines(&players)
-        } else {
-            vec![]
+        host: normalise_name(reqd_form_value(form_data, "host")?),
+        players: display_names,
+        player_ids: players.iter().map(|p| p.player_id.clone()).collect(),
+        tables,
+        ranking_method: match form_value(form_data, "rankingMethod") {
+            Some(s) if !s.is_empty() => s.parse::<RankingMethod>()?,
+            _ => RankingMethod::Total,
         },
-        tables: if let Some(tables) = form_value(form_data, "tables") {
-            lines(&tables)
-        } else {
-            vec![]
+        confirmation: match form_value(form_data, "confirmation") {
+            Some(s) if !s.is_empty() => s.parse::<Confirmation>()?,
+            _ => Confirmation::Off,
+        },
+        // Games are started and finished by their own actions, not by editing them.
+        status: GameStatus::Planned,
+        final_standings: vec![],
+        archived: bool_form_value(form_data, "archived"),
+        version: version_form_value(form_data)?,
+    };
+    Ok((game, changed_players))
+}
+
+/// Updates a registry player's display name and aliases, which mustn't be another player's name.
+pub fn form_data_to_player(player: Player, form_data: &[(String, String)], registry: &[Player]) -> Result<Player, ValidationError> {
+    let display_name = normalise_name(reqd_form_value(form_data, "displayName")?);
+    if display_name.is_empty() {
+        return Err(ValidationError { msg: "Missing required field: displayName".to_string() });
+    }
+    let aliases = form_value(form_data, "aliases").map(|s| lines(s)).unwrap_or_default();
+
+    for name in std::iter::once(&display_name).chain(aliases.iter()) {
+        if let Some(other) = registry.iter().find(|p| p.player_id != player.player_id && p.is_called(name)) {
+            return Err(ValidationError { msg: format!("{} is already used by {}", name, other.display_name) });
         }
-    })
+    }
+
+    Ok(Player { display_name, aliases, ..player })
 }
 
-pub fn form_data_to_hand(form_data: &Vec<(String, String)>) -> Result<CompletedHand, ValidationError> {
+/// Builds a hand from its form, with players and table matched to how the game spells them.
+pub fn form_data_to_hand(form_data: &[(String, String)], game: &Game) -> Result<CompletedHand, ValidationError> {
     let hand_id = reqd_form_value(form_data, "handId")?;
     let (hand_number, table) = hand_number_and_table(hand_id)?;
+    let table = canonical_name(&game.tables, &table, "table")?.clone();
+    let player = |name: &str| canonical_name(&game.players, name, "player").cloned();
 
-    let bidder = reqd_form_value(form_data, "bidder")?.clone();
+    let bidder = player(reqd_form_value(form_data, "bidder")?)?;
     let partner = match form_value(form_data, "partner") {
         None => None,
         Some(s) if s.is_empty() => None,
-        Some(s) => Some(s.clone())
+        Some(s) => Some(player(s)?)
     };
-    let defence: Vec<String> = form_values(form_data, "defence").iter().map(|s| (*s).clone()).collect();
+    let defence: Vec<String> = form_values(form_data, "defence").iter().map(|s| player(s)).collect::<Result<_, _>>()?;
     if defence.is_empty() {
         return Err(ValidationError { msg: "Missing required field: defence".to_string() });
     }
@@ -72,6 +175,9 @@ pub fn form_data_to_hand(form_data: &Vec<(String, String)>) -> Result<CompletedH
     if let Some(ref partner) = partner {
         players.push(partner.clone());
     }
+    if let Some((a, _)) = find_near_duplicate(&players) {
+        return Err(ValidationError { msg: format!("{} is in the hand twice", a) });
+    }
 
     let bid = reqd_form_value(form_data, "bid")?.parse::<Bid>().unwrap();
     let won = bool_form_value(form_data, "won");
@@ -81,6 +187,8 @@ pub fn form_data_to_hand(form_data: &Vec<(String, String)>) -> Result<CompletedH
     let petit_au_bout = bool_form_value(form_data, "petitAuBout");
     let poignee = reqd_form_value(form_data, "poignee")?.parse::<Poignée>().unwrap();
     let chelem = reqd_form_value(form_data, "chelem")?.parse::<Chelem>().unwrap();
+    let notes = form_value(form_data, "notes").map(|s| s.trim().to_string()).unwrap_or_default();
+    let disputed = bool_form_value(form_data, "disputed");
 
     Ok(CompletedHand {
         table,
@@ -95,5 +203,52 @@ pub fn form_data_to_hand(form_data: &Vec<(String, String)>) -> Result<CompletedH
         petit_au_bout,
         poignee,
         chelem,
+        notes,
+        disputed,
+        version: version_form_value(form_data)?,
+        deleted_at: None,
+        created_at: None,
+        updated_at: None,
+        entered_by: None,
+        confirmed_by: None,
+        entered_device: None,
     })
-}
\ No newline at end of file
+}
+
+/// Whether `s` is a date as forms give them, `YYYY-MM-DD`.
+fn is_date(s: &str) -> bool {
+    s.len() == 10 && s.char_indices().all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() })
+}
+
+/// Reads the games search form, which is submitted as a query string.
+pub fn query_to_game_search(query_params: &[(String, String)]) -> Result<GameSearch, ValidationError> {
+    let value = |key| form_value(query_params, key).map(|s| normalise_name(s)).unwrap_or_default();
+    let search = GameSearch {
+        host: value("host"),
+        player: value("player"),
+        from: value("from"),
+        to: value("to"),
+    };
+    for date in [&search.from, &search.to] {
+        if !date.is_empty() && !is_date(date) {
+            return Err(ValidationError { msg: format!("Invalid date: {}", date) });
+        }
+    }
+    if !search.from.is_empty() && !search.to.is_empty() && search.from > search.to {
+        return Err(ValidationError { msg: format!("{} is after {}", search.from, search.to) });
+    }
+    Ok(search)
+}
+
+/// Reads the cursor for a page of games after the first, `{date}/{gameId}` as `list_games` gives it.
+pub fn query_to_cursor(query_params: &[(String, String)]) -> Result<Option<(String, String)>, ValidationError> {
+    let Some(cursor) = form_value(query_params, "cursor") else {
+        return Ok(None);
+    };
+    let invalid = || ValidationError { msg: format!("Invalid cursor: {}", cursor) };
+    let (date, game_id) = cursor.split_once('/').ok_or_else(invalid)?;
+    if !is_date(date) || game_id.is_empty() {
+        return Err(invalid());
+    }
+    Ok(Some((date.to_string(), game_id.to_string())))
+}
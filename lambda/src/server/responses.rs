@@ -1,50 +1,88 @@
-use std::collections::HashMap;
-
 use http::{Error as HttpError, Method, Response as HttpResponse};
 use image::{ImageEncoder, Luma};
 use lambda_http::Body;
 use maud::Markup;
 use qrcode::QrCode;
 
-use crate::game::{CompletedHand, Game};
-use crate::html::{html_edit_hand, html_game, html_game_not_found, html_hand_not_found, html_new_or_edit_game, html_method_not_allowed, html_not_found, html_validation_error};
+use crate::game::{CompletedHand, Game, GameSearch, Player};
+use crate::history::HistoryEntry;
+use crate::html::{html_edit_hand, html_index, html_game, html_game_admin, html_game_history, html_game_conflict, html_game_locked, html_suspected_duplicate, html_hand_conflict, html_game_not_found, html_game_table, html_hand_not_found, html_new_game_like, html_new_or_edit_game, html_method_not_allowed, html_not_found, html_players, html_table_not_found, html_validation_error};
+use crate::scoring::{GameListing, GameSummary};
+use crate::stats::{PlayerStats, SessionTiming};
 use crate::server::routes::{url_for, Route};
 
 #[derive(Debug)]
 pub enum Response {
-    CreateGamePage,
+    IndexPage { games: Vec<GameListing>, search: GameSearch, next_cursor: Option<String>, registry: Vec<Player> },
+    EditGamePage { game: Game, registry: Vec<Player> },
+    NewGameLikePage { template: Game, registry: Vec<Player> },
+    GameAdminPage { game: Game, hands: Vec<CompletedHand>, same_day_games: Vec<Game> },
+    GameHistoryPage { game: Game, entries: Vec<HistoryEntry> },
     RedirectToGame { game: Game },
+    Redirect { location: String },
+    /// `deleted` is the hand just moved to the trash, if any, so that it can be restored.
     GamePage {
         game: Game,
-        hands_with_scores: Vec<(CompletedHand, HashMap<String, i32>)>,
-        total_scores: HashMap<String, i32>,
-        player_hand_count: HashMap<String, i32>
+        summary: GameSummary,
+        stats: Vec<PlayerStats>,
+        timing: SessionTiming,
+        uncounted: Vec<CompletedHand>,
+        trash: Vec<CompletedHand>,
+        deleted: Option<CompletedHand>,
     },
     GameNotFound { game_id: String },
+    /// `uncounted` are the hands left out of `summary` until they're confirmed.
+    GameTablePage { game: Game, table: String, summary: GameSummary, uncounted: Vec<CompletedHand> },
+    TableNotFound { game_id: String, table: String },
     EditHandPage { game: Game, hands: Vec<CompletedHand>, hand: CompletedHand },
     HandNotFound { game_id: String, hand_id: String },
+    /// Someone else saved the hand first: `theirs` is what they saved, if they didn't delete it.
+    HandConflict { game: Game, mine: CompletedHand, theirs: Option<CompletedHand>, old_hand_id: Option<String> },
+    GameConflict { mine: Game, theirs: Game },
+    /// The game is finished, so it can't be changed until it's reopened.
+    GameLocked { game: Game },
+    /// `mine` looks like `duplicate` entered again: confirming posts it to `action` again.
+    SuspectedDuplicate { game: Game, mine: CompletedHand, duplicate: CompletedHand, action: Route, passthrough: Vec<(String, String)> },
     NotFound,
     HttpMethodNotAllowed { method: Method, path: String },
     ValidationError { msg: String },
-    QRCode { domain_name: String, game_id: String },
+    QRCode { domain_name: String, route: Route },
+    PlayersPage { players: Vec<Player> },
 }
 
 pub fn render(response: Response) -> Result<HttpResponse<Body>, HttpError> {
     match response {
-        Response::CreateGamePage => http200(html_new_or_edit_game(None)),
+        Response::IndexPage { games, search, next_cursor, registry } => {
+            http200(html_index(&games, &search, next_cursor.as_deref(), &registry))
+        },
+        Response::EditGamePage { game, registry } => http200(html_new_or_edit_game(Some(&game), &registry)),
+        Response::NewGameLikePage { template, registry } => http200(html_new_game_like(&template, &registry)),
+        Response::GameAdminPage { game, hands, same_day_games } => http200(html_game_admin(&game, &hands, &same_day_games)),
+        Response::GameHistoryPage { game, entries } => http200(html_game_history(&game, &entries)),
         Response::RedirectToGame { game } => http302(url_for(&Route::Game { game_id: game.game_id })),
-        Response::GamePage { game, hands_with_scores, total_scores, player_hand_count } => {
-            http200(html_game(&game, &hands_with_scores, &total_scores, &player_hand_count))
+        Response::Redirect { location } => http302(location),
+        Response::GamePage { game, summary, stats, timing, uncounted, trash, deleted } => {
+            http200(html_game(&game, &summary, &stats, &timing, &uncounted, &trash, deleted.as_ref()))
         },
         Response::GameNotFound { game_id } => {
             http404(html_game_not_found(&game_id))
         },
+        Response::GameTablePage { game, table, summary, uncounted } => http200(html_game_table(&game, &table, &summary, &uncounted)),
+        Response::TableNotFound { game_id, table } => {
+            http404(html_table_not_found(&game_id, &table))
+        },
         Response::EditHandPage { game, hands, hand } => {
             http200(html_edit_hand(&game, &hands, &hand))
         },
         Response::HandNotFound { game_id, hand_id } => {
             http404(html_hand_not_found(&game_id, &hand_id))
         },
+        Response::HandConflict { game, mine, theirs, old_hand_id } => http409(html_hand_conflict(&game, &mine, theirs.as_ref(), old_hand_id.as_deref())),
+        Response::GameConflict { mine, theirs } => http409(html_game_conflict(&mine, &theirs)),
+        Response::GameLocked { game } => http423(html_game_locked(&game)),
+        Response::SuspectedDuplicate { game, mine, duplicate, action, passthrough } => {
+            http200(html_suspected_duplicate(&game, &mine, &duplicate, &action, &passthrough))
+        },
         Response::NotFound => http404(html_not_found()),
         Response::HttpMethodNotAllowed { method, path } => {
             http405(html_method_not_allowed(&method, &path))
@@ -52,8 +90,9 @@ pub fn render(response: Response) -> Result<HttpResponse<Body>, HttpError> {
         Response::ValidationError { msg } => {
             http400(html_validation_error(&msg))
         }
-        Response::QRCode { domain_name, game_id } => {
-            let url = format!("https://{}{}", domain_name, url_for(&Route::Game { game_id: game_id }));
+        Response::PlayersPage { players } => http200(html_players(&players)),
+        Response::QRCode { domain_name, route } => {
+            let url = format!("https://{}{}", domain_name, url_for(&route));
             let code = QrCode::new(url.as_bytes()).unwrap();
             let image = code
                 .render::<Luma<u8>>()
@@ -112,3 +151,17 @@ fn http405(html: Markup) -> Result<HttpResponse<Body>, HttpError> {
         .header("Content-Type", "text/html; charset=utf-8")
         .body(html.into_string().into())
 }
+
+fn http409(html: Markup) -> Result<HttpResponse<Body>, HttpError> {
+    HttpResponse::builder()
+        .status(409)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(html.into_string().into())
+}
+
+fn http423(html: Markup) -> Result<HttpResponse<Body>, HttpError> {
+    HttpResponse::builder()
+        .status(423)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .body(html.into_string().into())
+}
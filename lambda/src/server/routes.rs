@@ -1,44 +1,112 @@
 use lazy_static::lazy_static;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
 use regex::Regex;
 
 // Route patterns
 lazy_static! {
     static ref ROUTE_GAME: Regex = Regex::new(r"^/games/([^/]+)$").unwrap();
+    static ref ROUTE_GAME_EDIT: Regex = Regex::new(r"^/games/([^/]+)/edit$").unwrap();
+    static ref ROUTE_GAME_CLONE: Regex = Regex::new(r"^/games/([^/]+)/clone$").unwrap();
+    static ref ROUTE_GAME_ADMIN: Regex = Regex::new(r"^/games/([^/]+)/admin$").unwrap();
+    static ref ROUTE_GAME_MERGE: Regex = Regex::new(r"^/games/([^/]+)/merge$").unwrap();
+    static ref ROUTE_GAME_SPLIT: Regex = Regex::new(r"^/games/([^/]+)/split$").unwrap();
+    static ref ROUTE_GAME_RENUMBER: Regex = Regex::new(r"^/games/([^/]+)/renumber$").unwrap();
+    static ref ROUTE_GAME_FINISH: Regex = Regex::new(r"^/games/([^/]+)/finish$").unwrap();
+    static ref ROUTE_GAME_REOPEN: Regex = Regex::new(r"^/games/([^/]+)/reopen$").unwrap();
+    static ref ROUTE_GAME_HISTORY: Regex = Regex::new(r"^/games/([^/]+)/history$").unwrap();
     static ref ROUTE_GAME_QRCODE: Regex = Regex::new(r"^/games/([^/]+)/qrcode$").unwrap();
     static ref ROUTE_HANDS: Regex = Regex::new(r"^/games/([^/]+)/hands$").unwrap();
     static ref ROUTE_HAND: Regex = Regex::new(r"^/games/([^/]+)/hands/([^/]+)$").unwrap();
+    static ref ROUTE_HAND_RESTORE: Regex = Regex::new(r"^/games/([^/]+)/hands/([^/]+)/restore$").unwrap();
+    static ref ROUTE_HAND_CONFIRM: Regex = Regex::new(r"^/games/([^/]+)/hands/([^/]+)/confirm$").unwrap();
+    static ref ROUTE_TABLE: Regex = Regex::new(r"^/games/([^/]+)/tables/([^/]+)$").unwrap();
+    static ref ROUTE_TABLE_QRCODE: Regex = Regex::new(r"^/games/([^/]+)/tables/([^/]+)/qrcode$").unwrap();
+    static ref ROUTE_PLAYER: Regex = Regex::new(r"^/players/([^/]+)$").unwrap();
 }
 
-fn match_route_pattern<'a>(pattern: &Regex, path: &'a str) -> Option<Vec<&'a str>> {
+fn match_route_pattern(pattern: &Regex, path: &str) -> Option<Vec<String>> {
     pattern.captures(path).map(|caps| {
-        caps.iter().skip(1).map(|m| m.unwrap().as_str()).collect()
+        caps.iter()
+            .skip(1)
+            .map(|m| percent_decode_str(m.unwrap().as_str()).decode_utf8_lossy().to_string())
+            .collect()
     })
 }
 
+/// Table names (and so hand ids) are free text, so they need escaping to be used as a path segment.
+fn path_segment(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+#[derive(Debug, Clone)]
 pub enum Route {
     Index,
     Games,
     Game { game_id: String },
+    GameEdit { game_id: String },
+    GameClone { game_id: String },
+    GameAdmin { game_id: String },
+    GameMerge { game_id: String },
+    GameSplit { game_id: String },
+    GameRenumber { game_id: String },
+    GameFinish { game_id: String },
+    GameReopen { game_id: String },
+    GameHistory { game_id: String },
     GameQRCode { game_id: String },
     GameHands { game_id: String },
-    GameHand { game_id: String, hand_id: String }
+    GameHand { game_id: String, hand_id: String },
+    GameHandRestore { game_id: String, hand_id: String },
+    GameHandConfirm { game_id: String, hand_id: String },
+    GameTable { game_id: String, table: String },
+    GameTableQRCode { game_id: String, table: String },
+    Players,
+    Player { player_id: String },
 }
 
 pub fn match_route(path: &str) -> Option<Route> {
-    if path == "/" || path == "" {
+    if path == "/" || path.is_empty() {
         Some(Route::Index)
     } else if path == "/games" {
         Some(Route::Games)
+    } else if path == "/players" {
+        Some(Route::Players)
     } else if let Some(caps) = match_route_pattern(&ROUTE_GAME, path) {
-        Some(Route::Game { game_id: caps[0].to_string() })
+        Some(Route::Game { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_EDIT, path) {
+        Some(Route::GameEdit { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_CLONE, path) {
+        Some(Route::GameClone { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_ADMIN, path) {
+        Some(Route::GameAdmin { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_MERGE, path) {
+        Some(Route::GameMerge { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_SPLIT, path) {
+        Some(Route::GameSplit { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_RENUMBER, path) {
+        Some(Route::GameRenumber { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_FINISH, path) {
+        Some(Route::GameFinish { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_REOPEN, path) {
+        Some(Route::GameReopen { game_id: caps[0].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_HISTORY, path) {
+        Some(Route::GameHistory { game_id: caps[0].clone() })
     } else if let Some(caps) = match_route_pattern(&ROUTE_GAME_QRCODE, path) {
-        Some(Route::GameQRCode { game_id: caps[0].to_string() })
+        Some(Route::GameQRCode { game_id: caps[0].clone() })
     } else if let Some(caps) = match_route_pattern(&ROUTE_HANDS, path) {
-        Some(Route::GameHands { game_id: caps[0].to_string() })
+        Some(Route::GameHands { game_id: caps[0].clone() })
     } else if let Some(caps) = match_route_pattern(&ROUTE_HAND, path) {
-        Some(Route::GameHand { game_id: caps[0].to_string(), hand_id: caps[1].to_string() })
+        Some(Route::GameHand { game_id: caps[0].clone(), hand_id: caps[1].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_HAND_RESTORE, path) {
+        Some(Route::GameHandRestore { game_id: caps[0].clone(), hand_id: caps[1].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_HAND_CONFIRM, path) {
+        Some(Route::GameHandConfirm { game_id: caps[0].clone(), hand_id: caps[1].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_TABLE, path) {
+        Some(Route::GameTable { game_id: caps[0].clone(), table: caps[1].clone() })
+    } else if let Some(caps) = match_route_pattern(&ROUTE_PLAYER, path) {
+        Some(Route::Player { player_id: caps[0].clone() })
     } else {
-        None
+        match_route_pattern(&ROUTE_TABLE_QRCODE, path)
+            .map(|caps| Route::GameTableQRCode { game_id: caps[0].clone(), table: caps[1].clone() })
     }
 }
 
@@ -47,8 +115,23 @@ pub fn url_for(route: &Route) -> String {
         Route::Index => "/".to_string(),
         Route::Games => "/games".to_string(),
         Route::Game { game_id } => format!("/games/{}", game_id),
+        Route::GameEdit { game_id } => format!("/games/{}/edit", game_id),
+        Route::GameClone { game_id } => format!("/games/{}/clone", game_id),
+        Route::GameAdmin { game_id } => format!("/games/{}/admin", game_id),
+        Route::GameMerge { game_id } => format!("/games/{}/merge", game_id),
+        Route::GameSplit { game_id } => format!("/games/{}/split", game_id),
+        Route::GameRenumber { game_id } => format!("/games/{}/renumber", game_id),
+        Route::GameFinish { game_id } => format!("/games/{}/finish", game_id),
+        Route::GameReopen { game_id } => format!("/games/{}/reopen", game_id),
+        Route::GameHistory { game_id } => format!("/games/{}/history", game_id),
         Route::GameQRCode { game_id } => format!("/games/{}/qrcode", game_id),
         Route::GameHands { game_id } => format!("/games/{}/hands", game_id),
-        Route::GameHand { game_id, hand_id } => format!("/games/{}/hands/{}", game_id, hand_id)
+        Route::GameHand { game_id, hand_id } => format!("/games/{}/hands/{}", game_id, path_segment(hand_id)),
+        Route::GameHandRestore { game_id, hand_id } => format!("/games/{}/hands/{}/restore", game_id, path_segment(hand_id)),
+        Route::GameHandConfirm { game_id, hand_id } => format!("/games/{}/hands/{}/confirm", game_id, path_segment(hand_id)),
+        Route::GameTable { game_id, table } => format!("/games/{}/tables/{}", game_id, path_segment(table)),
+        Route::GameTableQRCode { game_id, table } => format!("/games/{}/tables/{}/qrcode", game_id, path_segment(table)),
+        Route::Players => "/players".to_string(),
+        Route::Player { player_id } => format!("/players/{}", player_id),
     }
-}
\ No newline at end of file
+}
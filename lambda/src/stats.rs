@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use crate::game::{Bid, Chelem, CompletedHand, Poignée};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractStats {
+    pub bid: Bid,
+    pub taken: i32,
+    pub won: i32,
+}
+
+impl ContractStats {
+    pub fn success_rate(&self) -> Option<f64> {
+        if self.taken == 0 {
+            None
+        } else {
+            Some(self.won as f64 / self.taken as f64)
+        }
+    }
+}
+
+/// One player's recap of an evening.
+///
+/// Petits au bout, poignées and chelems aren't recorded against a particular player, so they're
+/// counted for everyone on the attacking side (bidder and partner) of the hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerStats {
+    pub player: String,
+    pub hands_played: i32,
+    pub as_bidder: i32,
+    pub as_partner: i32,
+    pub as_defender: i32,
+    /// Contracts taken as bidder, by bid level.
+    pub contracts: Vec<ContractStats>,
+    /// Average points won (positive) or lost (negative) by on contracts taken as bidder.
+    pub average_margin: Option<f64>,
+    pub petits_au_bout: i32,
+    pub poignees: i32,
+    pub chelems: i32,
+    pub biggest_win: Option<i32>,
+    pub biggest_loss: Option<i32>,
+}
+
+impl PlayerStats {
+    fn new(player: &str) -> PlayerStats {
+        PlayerStats {
+            player: player.to_string(),
+            hands_played: 0,
+            as_bidder: 0,
+            as_partner: 0,
+            as_defender: 0,
+            contracts: [Bid::Petite, Bid::Garde, Bid::GardeSans, Bid::GardeContre]
+                .into_iter()
+                .map(|bid| ContractStats { bid, taken: 0, won: 0 })
+                .collect(),
+            average_margin: None,
+            petits_au_bout: 0,
+            poignees: 0,
+            chelems: 0,
+            biggest_win: None,
+            biggest_loss: None,
+        }
+    }
+
+    pub fn contracts_taken(&self) -> i32 {
+        self.contracts.iter().map(|c| c.taken).sum()
+    }
+
+    pub fn contracts_won(&self) -> i32 {
+        self.contracts.iter().map(|c| c.won).sum()
+    }
+}
+
+/// Statistics for each of `players` over a game's scored hands, in the order given.
+pub fn player_stats(players: &[String], hands_with_scores: &[(CompletedHand, HashMap<String, i32>)]) -> Vec<PlayerStats> {
+    players
+        .iter()
+        .map(|player| {
+            let mut stats = PlayerStats::new(player);
+            let mut margins = vec![];
+
+            for (hand, scores) in hands_with_scores {
+                let score = match scores.get(player) {
+                    Some(score) => *score,
+                    None => continue,
+                };
+                stats.hands_played += 1;
+
+                let is_bidder = hand.bidder == *player;
+                let is_partner = hand.partner.as_ref() == Some(player) && !is_bidder;
+                if is_bidder {
+                    stats.as_bidder += 1;
+                    let contract = stats.contracts.iter_mut().find(|c| c.bid == hand.bid).unwrap();
+                    contract.taken += 1;
+                    if hand.won {
+                        contract.won += 1;
+                    }
+                    margins.push(if hand.won { hand.won_or_lost_by } else { -hand.won_or_lost_by });
+                } else if is_partner {
+                    stats.as_partner += 1;
+                } else {
+                    stats.as_defender += 1;
+                }
+
+                if is_bidder || is_partner {
+                    if hand.petit_au_bout {
+                        stats.petits_au_bout += 1;
+                    }
+                    if hand.poignee != Poignée::Aucune {
+                        stats.poignees += 1;
+                    }
+                    if hand.chelem != Chelem::Aucun {
+                        stats.chelems += 1;
+                    }
+                }
+
+                if score > 0 && stats.biggest_win.is_none_or(|best| score > best) {
+                    stats.biggest_win = Some(score);
+                }
+                if score < 0 && stats.biggest_loss.is_none_or(|worst| score < worst) {
+                    stats.biggest_loss = Some(score);
+                }
+            }
+
+            if !margins.is_empty() {
+                stats.average_margin = Some(margins.iter().sum::<i32>() as f64 / margins.len() as f64);
+            }
+            stats
+        })
+        .collect()
+}
+
+/// How fast a table played.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TablePace {
+    pub table: String,
+    /// Average minutes from one hand being entered to the next, once two or more have been.
+    pub minutes_per_hand: Option<f64>,
+}
+
+/// How long an evening lasted and how fast each table played, going by when hands were entered.
+/// Hands saved before entry times were recorded are left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionTiming {
+    /// Minutes from the first hand entered to the last.
+    pub session_minutes: Option<i64>,
+    pub tables: Vec<TablePace>,
+}
+
+pub fn session_timing(tables: &[String], hands: &[CompletedHand]) -> SessionTiming {
+    let span = |times: &[i64]| match (times.iter().min(), times.iter().max()) {
+        (Some(first), Some(last)) if times.len() > 1 => Some(last - first),
+        _ => None,
+    };
+
+    let times: Vec<i64> = hands.iter().filter_map(|h| h.created_secs()).collect();
+    SessionTiming {
+        session_minutes: span(&times).map(|secs| secs / 60),
+        tables: tables
+            .iter()
+            .map(|table| {
+                let times: Vec<i64> = hands.iter().filter(|h| h.table == *table).filter_map(|h| h.created_secs()).collect();
+                TablePace {
+                    table: table.clone(),
+                    minutes_per_hand: span(&times).map(|secs| secs as f64 / 60.0 / (times.len() - 1) as f64),
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::score_hands;
+
+    fn hand(bid: Bid, bidder: &str, partner: Option<&str>, won: bool, won_or_lost_by: i32) -> CompletedHand {
+        let players = ["Alice", "Bob", "Charlie", "David", "Eve"];
+        CompletedHand { bid, won, won_or_lost_by, ..CompletedHand::test(&players, bidder, partner) }
+    }
+
+    #[test]
+    fn test_player_stats() {
+        let mut with_poignee = hand(Bid::Garde, "Alice", Some("Bob"), true, 10);
+        with_poignee.poignee = Poignée::Simple;
+        let hands = vec![
+            with_poignee,
+            hand(Bid::Petite, "Alice", None, false, 4),
+            hand(Bid::Garde, "Charlie", Some("Alice"), true, 0),
+        ];
+        let (hands_with_scores, _, _) = score_hands(hands).unwrap();
+        let stats = player_stats(&["Alice".to_string(), "Bob".to_string()], &hands_with_scores);
+
+        let alice = &stats[0];
+        assert_eq!((alice.hands_played, alice.as_bidder, alice.as_partner, alice.as_defender), (3, 2, 1, 0));
+        assert_eq!((alice.contracts_taken(), alice.contracts_won()), (2, 1));
+        assert_eq!(alice.contracts[0], ContractStats { bid: Bid::Petite, taken: 1, won: 0 });
+        assert_eq!(alice.contracts[1].success_rate(), Some(1.0));
+        assert_eq!(alice.average_margin, Some(3.0)); // (10 - 4) / 2
+        assert_eq!(alice.poignees, 1);
+        assert_eq!(alice.biggest_win, Some(2 * ((25 + 10) * 2 + 20)));
+        assert_eq!(alice.biggest_loss, Some(-4 * (25 + 4)));
+
+        let bob = &stats[1];
+        assert_eq!((bob.as_bidder, bob.as_partner, bob.as_defender), (0, 1, 2));
+        assert_eq!(bob.average_margin, None);
+        assert_eq!(bob.poignees, 1);
+    }
+
+    #[test]
+    fn test_session_timing() {
+        let at = |table: &str, created_at: Option<&str>| CompletedHand {
+            table: table.to_string(),
+            created_at: created_at.map(|t| t.to_string()),
+            ..hand(Bid::Petite, "Alice", None, true, 0)
+        };
+        let hands = vec![
+            at("Atout", Some("2025-03-14T20:30:00Z")),
+            at("Atout", Some("2025-03-14T20:42:00Z")),
+            at("Atout", Some("2025-03-14T20:51:00Z")),
+            at("Excuse", Some("2025-03-14T22:05:00Z")),
+            at("Excuse", None),
+        ];
+        let timing = session_timing(&["Atout".to_string(), "Excuse".to_string()], &hands);
+        assert_eq!(timing.session_minutes, Some(95));
+        assert_eq!(timing.tables[0].minutes_per_hand, Some(10.5));
+        assert_eq!(timing.tables[1].minutes_per_hand, None);
+    }
+}